@@ -0,0 +1,22 @@
+//! A local WebSocket echo server for trying out `quasar::ws` channels
+//!
+//! Run it with `cargo run` from `examples/echo_server`, then connect to it from an app:
+//!
+//! ```ignore
+//! let channel = app.connect("ws://127.0.0.1:3012", "messages", |messages: &mut Vec<String>, msg: String| {
+//!     messages.push(msg);
+//! });
+//! ```
+//!
+//! Every message is sent back to the socket it came from. Stopping and restarting the server
+//!   exercises the channel's reconnection backoff.
+
+extern crate ws;
+
+fn main() {
+    let addr = std::env::args().nth(1).unwrap_or_else(|| "127.0.0.1:3012".to_owned());
+    println!("Echoing WebSocket messages on ws://{}", addr);
+    if let Err(err) = ws::listen(&*addr, |out| move |msg| out.send(msg)) {
+        println!("Echo server failed: {}", err);
+    }
+}
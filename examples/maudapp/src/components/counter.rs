@@ -1,16 +1,17 @@
 use quasar::*;
+use maud::Markup;
 
 #[derive(Default)]
 pub struct CounterData {
     count: u32,
 }
 
-impl Renderable for CounterData {
-    fn render(&self, _node: &Node, _app: &AppContext) -> String {
-        (html! {
+impl RenderMarkup for CounterData {
+    fn markup(&self, _node: &Node, _app: &AppContext) -> Markup {
+        html! {
             p { "Count: " (self.count) }
             button { "+1" }
-        }).into_string()
+        }
     }
 }
 
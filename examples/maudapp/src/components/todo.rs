@@ -1,7 +1,9 @@
 use quasar::*;
+use maud::Markup;
 
 #[derive(Default)]
 pub struct TodoItem {
+    id: u32,
     label: String,
     complete: bool,
 }
@@ -9,42 +11,48 @@ pub struct TodoItem {
 #[derive(Default)]
 pub struct TodoList {
     items: Vec<TodoItem>,
+    next_id: u32,
 }
 
-impl Renderable for TodoList {
-    fn render(&self, _node: &Node, _app: &AppContext) -> String {
-        (html! {
+impl RenderMarkup for TodoList {
+    fn markup(&self, _node: &Node, _app: &AppContext) -> Markup {
+        html! {
             h3 { "To Do List (" (self.items.len()) " items)" }
             ul id="todo-ul" {
                 @for item in &self.items {
-                    li class={ "todo-item " (item.complete) } {
+                    li class={ "todo-item " (item.complete) } data-key=(item.id) {
                         input type="checkbox" checked?[item.complete]
                         (item.label)
                     }
                 }
             }
-            input id="message" type="text"
+            input ref="message" type="text"
             button { "Add" }
-        }).into_string()
+        }
     }
 }
 
 impl Component for TodoList {
     fn onload(view: &View<Self>) {
         view.on_each(EventType::Click, "button", |mut evt| {
-            match evt.binding.query("#message") {
-                Some(node) => {
-                    let item = TodoItem { label: node.get("value"), complete: false };
-                    evt.binding.data_mut().items.push(item);
+            match evt.binding.r#ref("message") {
+                Ok(node) => {
+                    let mut list = evt.binding.data_mut();
+                    let item = TodoItem { id: list.next_id, label: node.value(), complete: false };
+                    list.next_id += 1;
+                    list.items.push(item);
                 }
-                None => println!("Query for #message returned nothing.")
+                Err(err) => warn!("{}", err),
             }
         });
 
         view.on_each(EventType::Change, ".todo-item input", |mut evt| {
             let state = evt.target.checked();
+            let key = evt.key.clone();
             let mut item_list = evt.binding.data_mut();
-            item_list.items[evt.index].complete = state;
+            if let Some(item) = key.and_then(|key| key.find_mut(&mut item_list.items, |item| item.id)) {
+                item.complete = state;
+            }
         });
     }
 }
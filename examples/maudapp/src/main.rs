@@ -1,6 +1,8 @@
 #![feature(plugin)]
 #![plugin(maud_macros)]
 
+#[macro_use]
+extern crate log;
 extern crate quasar;
 extern crate maud;
 
@@ -1,32 +1,25 @@
 use quasar::*;
-use helper::RuntimeComponent;
 
 #[derive(Debug, RustcEncodable)]
 pub struct CounterData {
     count: u32,
 }
 
-type CounterComponent = RuntimeComponent<CounterData, ::mustache::Template>;
+pub fn init() -> Result<RuntimeComponent<CounterData>> {
+    let data = CounterData {
+        count: 0
+    };
 
-pub fn init() -> CounterComponent  {
-    RuntimeComponent {
-        data: CounterData {
-            count: 0
-        },
-
-        props: vec![],
-
-        template: compile_str(r##"
-            <p>Count: {{count}}</p>
-            <button>+1</button>
-        "##).expect("failed to compile counter template")
-    }
+    RuntimeComponent::new(data, r##"
+        <p>Count: {{count}}</p>
+        <button>+1</button>
+    "##)
 }
 
-impl Component for CounterComponent {
-    fn onload(view: &View<Self>) {
+impl RuntimeData for CounterData {
+    fn onload(view: &View<RuntimeComponent<Self>>) {
         view.on_each(EventType::Click, "button", |mut evt| {
             evt.binding.data_mut().count += 1;
         });
     }
-}
\ No newline at end of file
+}
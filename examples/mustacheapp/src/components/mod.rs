@@ -3,14 +3,14 @@ mod counter;
 // mod cat_list;
 mod todo;
 
-use quasar::{QuasarApp, Queryable};
+use quasar::{QuasarApp, Queryable, Result};
 
-pub fn init(app: &QuasarApp) {
-    app.bind("#hello", hello::init());
-    app.bind("#counter", counter::init());
+pub fn init(app: &QuasarApp) -> Result<()> {
+    app.try_bind("#hello", hello::init()?)?;
+    app.try_bind("#counter", counter::init()?)?;
 
-    // app.bind(".cat-list", cat_list::init());
+    // app.try_bind(".cat-list", cat_list::init()?)?;
 
-    app.bind("#todo-list", todo::init());
-
-}
\ No newline at end of file
+    app.try_bind("#todo-list", todo::init()?)?;
+    Ok(())
+}
@@ -1,8 +1,8 @@
 use quasar::*;
-use helper::RuntimeComponent;
 
 #[derive(Debug, Default, RustcEncodable)]
 pub struct TodoItem {
+    id: u32,
     label: String,
     complete: bool,
 }
@@ -10,46 +10,46 @@ pub struct TodoItem {
 #[derive(Debug, Default, RustcEncodable)]
 pub struct TodoList {
     items: Vec<TodoItem>,
+    next_id: u32,
 }
 
-type TodoComponent = RuntimeComponent<TodoList, ::mustache::Template>;
-
-pub fn init() -> TodoComponent  {
-    RuntimeComponent {
-        props: vec![],
-        data: TodoList::default(),
-        template: compile_str(r##"
+pub fn init() -> Result<RuntimeComponent<TodoList>> {
+    RuntimeComponent::new(TodoList::default(), r##"
           <h3>To Do List</h3>
           <ul id="todo-list">
             {{#items}}
-                <li class="todo-item {{#complete}}complete{{/complete}}">
+                <li class="todo-item {{#complete}}complete{{/complete}}" data-key="{{ id }}">
                     <input type="checkbox" {{#complete}}checked{{/complete}}>
                     {{ label }}
                 </li>
             {{/items}}
           </ul>
-          <input id="message" type="text">
+          <input ref="message" type="text">
           <button>Add</button>
-        "##).expect("failed to compile todo_list template"),
-    }
+    "##)
 }
 
-impl Component for TodoComponent {
-    fn onload(view: &View<Self>) {
+impl RuntimeData for TodoList {
+    fn onload(view: &View<RuntimeComponent<Self>>) {
         view.on_each(EventType::Click, "button", |mut evt| {
-            match evt.binding.query("#message") {
-                Some(node) => {
-                    let item = TodoItem { label: node.get("value"), complete: false };
-                    evt.binding.data_mut().items.push(item);
+            match evt.binding.r#ref("message") {
+                Ok(node) => {
+                    let mut list = evt.binding.data_mut();
+                    let item = TodoItem { id: list.next_id, label: node.value(), complete: false };
+                    list.next_id += 1;
+                    list.items.push(item);
                 }
-                None => println!("Query for #message returned nothing.")
+                Err(err) => warn!("{}", err),
             }
         });
 
         view.on_each(EventType::Change, ".todo-item input", |mut evt| {
             let state = evt.target.checked();
+            let key = evt.key.clone();
             let mut item_list = evt.binding.data_mut();
-            item_list.items[evt.index].complete = state;
+            if let Some(item) = key.and_then(|key| key.find_mut(&mut item_list.items, |item| item.id)) {
+                item.complete = state;
+            }
         });
     }
 }
\ No newline at end of file
@@ -1,14 +1,15 @@
+#[macro_use]
+extern crate log;
 extern crate quasar;
-extern crate mustache;
 extern crate rustc_serialize;
 mod components;
-mod helper;
-pub use helper::RuntimeComponent;
 
 fn main() {
     let app = quasar::init();
     println!("Starting...");
 
-    components::init(&app);
-    app.spin();
+    match components::init(&app) {
+        Ok(()) => app.spin(),
+        Err(err) => error!("Failed to start: {}", err),
+    }
 }
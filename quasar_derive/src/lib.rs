@@ -0,0 +1,248 @@
+//! Custom derive for quasar's built-in template engine
+//!
+//! Enabled in quasar by the `derive` feature, which re-exports it as `quasar::Template`:
+//!
+//! ```ignore
+//! extern crate quasar;
+//! use quasar::Template;
+//!
+//! #[derive(Default, RustcEncodable, Template)]
+//! #[template = "src/counter.html"]
+//! struct CounterData { count: u32 }
+//! ```
+//!
+//! Template paths are relative to the crate root (`CARGO_MANIFEST_DIR`), or the template may be
+//!   inlined with `#[template_string = "..."]`. Partials used by `{{> name}}` tags are registered
+//!   with `#[template_partials(name = "src/partial.html")]`.
+//!
+//! Templates are parsed when the derive expands, so a malformed template or a partial tag
+//!   naming an unregistered partial is a compile error.
+//!
+//! The parsed template is built once per thread and cached by the type's `TypeId`, so the
+//!   generated impl bounds generic type parameters by `'static` (as well as `Encodable`, like
+//!   `#[derive(RustcEncodable)]`), and the type can't have lifetime parameters.
+
+extern crate proc_macro;
+extern crate syn;
+#[macro_use]
+extern crate quote;
+// Shared with quasar, so templates are parsed here exactly as `Template::compile` parses them
+extern crate quasar_template as parser;
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use proc_macro::TokenStream;
+use parser::Part;
+
+#[proc_macro_derive(Template, attributes(template, template_string, template_partials))]
+pub fn derive_template(input: TokenStream) -> TokenStream {
+    let source = input.to_string();
+    let ast = syn::parse_derive_input(&source).unwrap();
+    let gen = match impl_template(&ast) {
+        Ok(gen) => gen,
+        Err(msg) => quote! { compile_error!(#msg); },
+    };
+    gen.parse().unwrap()
+}
+
+// A template's parts, along with the file it was read from (if any)
+struct Source {
+    parts: Vec<Part>,
+    path: Option<String>,
+}
+
+// Read and parse a template at a path relative to the crate root
+fn read_template(path: &str) -> Result<Source, String> {
+    let root = env::var("CARGO_MANIFEST_DIR").map_err(|_| "CARGO_MANIFEST_DIR is not set".to_owned())?;
+    let mut src = String::new();
+    File::open(Path::new(&root).join(path))
+        .and_then(|mut file| file.read_to_string(&mut src))
+        .map_err(|err| format!("Failed to read template '{}': {}", path, err))?;
+    let parts = parser::parse(&src).map_err(|err| format!("Invalid template '{}': {}", path, err))?;
+    Ok(Source { parts: parts, path: Some(path.to_owned()) })
+}
+
+fn parse_template_string(src: &str) -> Result<Source, String> {
+    let parts = parser::parse(src).map_err(|err| format!("Invalid #[template_string]: {}", err))?;
+    Ok(Source { parts: parts, path: None })
+}
+
+fn impl_template(ast: &syn::DeriveInput) -> Result<quote::Tokens, String> {
+    let name = &ast.ident;
+
+    let mut template = None;
+    let mut partials = vec![];
+    for attr in &ast.attrs {
+        match attr.value {
+            syn::MetaItem::NameValue(ref ident, syn::Lit::Str(ref path, _)) if ident == "template" => {
+                template = Some(read_template(path)?);
+            }
+            syn::MetaItem::NameValue(ref ident, syn::Lit::Str(ref src, _)) if ident == "template_string" => {
+                template = Some(parse_template_string(src)?);
+            }
+            syn::MetaItem::List(ref ident, ref items) if ident == "template_partials" => {
+                for item in items {
+                    match *item {
+                        syn::NestedMetaItem::MetaItem(syn::MetaItem::NameValue(ref partial, syn::Lit::Str(ref path, _))) => {
+                            partials.push((partial.as_ref().to_owned(), read_template(path)?));
+                        }
+                        _ => return Err("#[template_partials] expects entries like `name = \"path/to/partial.html\"`".to_owned()),
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let template = template.ok_or_else(|| {
+        "#[derive(Template)] requires a #[template = \"...\"] or #[template_string = \"...\"] attribute".to_owned()
+    })?;
+
+    // Partials may render each other, so every partial tag must name a registered partial
+    let mut used = vec![];
+    partial_names(&template.parts, &mut used);
+    for &(_, ref partial) in &partials {
+        partial_names(&partial.parts, &mut used);
+    }
+    if let Some(missing) = used.iter().find(|used| !partials.iter().any(|&(ref name, _)| name == *used)) {
+        return Err(format!("No partial named '{}' is registered with #[template_partials]", missing));
+    }
+
+    // Rebuild when a template file changes
+    let includes: Vec<_> = Some(&template).into_iter()
+        .chain(partials.iter().map(|&(_, ref partial)| partial))
+        .filter_map(|source| source.path.as_ref())
+        .map(|path| quote! { let _ = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/", #path)); })
+        .collect();
+
+    let parts = parts_tokens(&template.parts);
+    let partials: Vec<_> = partials.iter().map(|&(ref name, ref partial)| {
+        let parts = parts_tokens(&partial.parts);
+        quote! { .partial(#name, ::quasar::template::Template::from_parts(#parts)) }
+    }).collect();
+
+    let mut generics = ast.generics.clone();
+    for param in &mut generics.ty_params {
+        param.bounds.push(syn::TyParamBound::Region(syn::Lifetime::new("'static")));
+        let encodable = syn::parse_path("::rustc_serialize::Encodable").unwrap();
+        param.bounds.push(syn::TyParamBound::Trait(syn::PolyTraitRef { bound_lifetimes: vec![], trait_ref: encodable },
+                                                   syn::TraitBoundModifier::None));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::quasar::Renderable for #name #ty_generics #where_clause {
+            fn render(&self, _node: &::quasar::Node, _app: &::quasar::AppContext) -> ::quasar::Result<String> {
+                #(#includes)*
+                let template = ::quasar::template::cached::<Self, _>(|| {
+                    ::quasar::template::Template::from_parts(#parts) #(#partials)*
+                });
+                template.render(self)
+            }
+        }
+    })
+}
+
+// The names of the partials rendered by `parts`
+fn partial_names(parts: &[Part], names: &mut Vec<String>) {
+    for part in parts {
+        match *part {
+            Part::If { ref then, ref otherwise, .. } => {
+                partial_names(then, names);
+                partial_names(otherwise, names);
+            }
+            Part::For { ref body, ref empty, .. } => {
+                partial_names(body, names);
+                partial_names(empty, names);
+            }
+            Part::Partial { ref name, .. } => names.push(name.clone()),
+            Part::Text(_) | Part::Value { .. } | Part::Child { .. } => (),
+        }
+    }
+}
+
+// Tokens evaluating to `parts` as quasar's `Vec<Part>`
+fn parts_tokens(parts: &[Part]) -> quote::Tokens {
+    let parts: Vec<_> = parts.iter().map(part_tokens).collect();
+    quote! { vec![#(#parts),*] }
+}
+
+fn part_tokens(part: &Part) -> quote::Tokens {
+    match *part {
+        Part::Text(ref text) => quote! { ::quasar::template::Part::Text(#text.to_owned()) },
+        Part::Value { ref path, escape } => {
+            let path = path_tokens(path);
+            quote! { ::quasar::template::Part::Value { path: #path, escape: #escape } }
+        }
+        Part::If { ref path, negate, ref then, ref otherwise } => {
+            let path = path_tokens(path);
+            let then = parts_tokens(then);
+            let otherwise = parts_tokens(otherwise);
+            quote! {
+                ::quasar::template::Part::If { path: #path, negate: #negate, then: #then, otherwise: #otherwise }
+            }
+        }
+        Part::For { ref var, ref path, ref body, ref empty } => {
+            let path = path_tokens(path);
+            let body = parts_tokens(body);
+            let empty = parts_tokens(empty);
+            quote! {
+                ::quasar::template::Part::For { var: #var.to_owned(), path: #path, body: #body, empty: #empty }
+            }
+        }
+        Part::Partial { ref name, ref path } => {
+            let path = match *path {
+                Some(ref path) => {
+                    let path = path_tokens(path);
+                    quote! { Some(#path) }
+                }
+                None => quote! { None },
+            };
+            quote! { ::quasar::template::Part::Partial { name: #name.to_owned(), path: #path } }
+        }
+        Part::Child { ref name } => quote! { ::quasar::template::Part::Child { name: #name.to_owned() } },
+    }
+}
+
+fn path_tokens(path: &[String]) -> quote::Tokens {
+    let segments: Vec<_> = path.iter().map(|segment| quote! { #segment.to_owned() }).collect();
+    quote! { vec![#(#segments),*] }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(input: &str) -> Result<String, String> {
+        let ast = syn::parse_derive_input(input).unwrap();
+        impl_template(&ast).map(|tokens| tokens.to_string())
+    }
+
+    #[test]
+    fn expands_to_parsed_parts() {
+        let gen = expand(r#"#[template_string = "<p>{{name}}</p>"] struct Hello { name: String }"#).unwrap();
+        assert!(gen.contains("Template :: from_parts"));
+        assert!(gen.contains("Part :: Value"));
+        assert!(!gen.contains("compile"));
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        let err = expand(r#"#[template_string = "{{#if a}}"] struct Hello;"#).unwrap_err();
+        assert!(err.contains("Unclosed 'if' block"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_unregistered_partials() {
+        let err = expand(r#"#[template_string = "{{#for item in items}}{{> item item}}{{/for}}"] struct List;"#)
+            .unwrap_err();
+        assert!(err.contains("No partial named 'item'"), "{}", err);
+    }
+
+    #[test]
+    fn requires_a_template() {
+        assert!(expand("struct Hello;").is_err());
+    }
+}
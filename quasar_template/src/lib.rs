@@ -0,0 +1,199 @@
+//! The template parser shared by quasar and quasar_derive
+//!
+//! `parse` turns template source into a tree of `Part`s: `quasar::template::Template::compile` parses
+//!   at runtime, and `#[derive(Template)]` parses as it expands, so both accept exactly the same syntax.
+//!
+//! Supported tags:
+//!
+//! ```text
+//! {{ path }}                  interpolate a value, HTML-escaped
+//! {{{ path }}}                interpolate a value without escaping
+//! {{#if path}} .. {{else}} .. {{/if}}          (`{{#if !path}}` negates)
+//! {{#for item in path}} .. {{else}} .. {{/for}}  ({{@index}} and {{@key}} within)
+//! {{> name}} or {{> name path}}  render a nested partial, optionally scoped to `path`
+//! {{< name}}                  placeholder for the child component bound as `name`
+//! {{! comment }}
+//! ```
+
+/// A dotted lookup like `item.label`; an empty path refers to the current scope (`.`)
+pub type Path = Vec<String>;
+
+/// A piece of a parsed template
+#[derive(Debug)]
+pub enum Part {
+    Text(String),
+    Value { path: Path, escape: bool },
+    If { path: Path, negate: bool, then: Vec<Part>, otherwise: Vec<Part> },
+    For { var: String, path: Path, body: Vec<Part>, empty: Vec<Part> },
+    Partial { name: String, path: Option<Path> },
+    Child { name: String },
+}
+
+enum Token<'a> {
+    Text(&'a str),
+    Tag(&'a str),
+    Raw(&'a str),
+}
+
+enum BlockKind {
+    Root,
+    If { path: Path, negate: bool },
+    For { var: String, path: Path },
+}
+
+struct Block {
+    kind: BlockKind,
+    parts: Vec<Part>,
+    otherwise: Option<Vec<Part>>,
+}
+
+impl Block {
+    fn new(kind: BlockKind) -> Block {
+        Block {
+            kind: kind,
+            parts: vec![],
+            otherwise: None,
+        }
+    }
+
+    fn push(&mut self, part: Part) {
+        match self.otherwise {
+            Some(ref mut otherwise) => otherwise.push(part),
+            None => self.parts.push(part),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self.kind {
+            BlockKind::Root => "template",
+            BlockKind::If { .. } => "if",
+            BlockKind::For { .. } => "for",
+        }
+    }
+}
+
+/// Parse template source, describing the first error found if it is malformed
+pub fn parse(src: &str) -> Result<Vec<Part>, String> {
+    let mut stack = vec![Block::new(BlockKind::Root)];
+
+    for token in tokenize(src)? {
+        let tag = match token {
+            Token::Text(text) => {
+                stack.last_mut().unwrap().push(Part::Text(text.to_owned()));
+                continue;
+            }
+            Token::Raw(path) => {
+                stack.last_mut().unwrap().push(Part::Value { path: parse_path(path)?, escape: false });
+                continue;
+            }
+            Token::Tag(tag) => tag,
+        };
+
+        if tag.starts_with('!') {
+            continue;
+        } else if tag.starts_with('#') {
+            let mut words = tag[1..].split_whitespace();
+            let kind = match (words.next(), words.next(), words.next(), words.next(), words.next()) {
+                (Some("if"), Some(path), None, None, None) => {
+                    let negate = path.starts_with('!');
+                    let path = if negate { &path[1..] } else { path };
+                    BlockKind::If { path: parse_path(path)?, negate: negate }
+                }
+                (Some("for"), Some(var), Some("in"), Some(path), None) => {
+                    BlockKind::For { var: var.to_owned(), path: parse_path(path)? }
+                }
+                _ => return Err(format!("Invalid block tag '{{{{{}}}}}'", tag)),
+            };
+            stack.push(Block::new(kind));
+        } else if tag == "else" {
+            let block = stack.last_mut().unwrap();
+            match (&block.kind, &block.otherwise) {
+                (&BlockKind::Root, _) => return Err("Unexpected '{{else}}' outside of a block".to_owned()),
+                (_, &Some(_)) => return Err(format!("Duplicate '{{{{else}}}}' in '{}' block", block.name())),
+                _ => (),
+            }
+            block.otherwise = Some(vec![]);
+        } else if tag.starts_with('/') {
+            let name = tag[1..].trim();
+            let block = stack.pop().unwrap();
+            if stack.is_empty() || block.name() != name {
+                return Err(format!("Unexpected '{{{{/{}}}}}' closing '{}' block", name, block.name()));
+            }
+            let otherwise = block.otherwise.unwrap_or_else(Vec::new);
+            let part = match block.kind {
+                BlockKind::If { path, negate } => {
+                    Part::If { path: path, negate: negate, then: block.parts, otherwise: otherwise }
+                }
+                BlockKind::For { var, path } => {
+                    Part::For { var: var, path: path, body: block.parts, empty: otherwise }
+                }
+                BlockKind::Root => unreachable!("root block is never closed by a tag"),
+            };
+            stack.last_mut().unwrap().push(part);
+        } else if tag.starts_with('>') {
+            let mut words = tag[1..].split_whitespace();
+            let part = match (words.next(), words.next(), words.next()) {
+                (Some(name), path, None) => {
+                    let path = match path {
+                        Some(path) => Some(parse_path(path)?),
+                        None => None,
+                    };
+                    Part::Partial { name: name.to_owned(), path: path }
+                }
+                _ => return Err(format!("Invalid partial tag '{{{{{}}}}}'", tag)),
+            };
+            stack.last_mut().unwrap().push(part);
+        } else if tag.starts_with('<') {
+            let mut words = tag[1..].split_whitespace();
+            let part = match (words.next(), words.next()) {
+                (Some(name), None) => Part::Child { name: name.to_owned() },
+                _ => return Err(format!("Invalid component tag '{{{{{}}}}}'", tag)),
+            };
+            stack.last_mut().unwrap().push(part);
+        } else {
+            stack.last_mut().unwrap().push(Part::Value { path: parse_path(tag)?, escape: true });
+        }
+    }
+
+    let root = stack.pop().unwrap();
+    if !stack.is_empty() {
+        return Err(format!("Unclosed '{}' block", root.name()));
+    }
+    Ok(root.parts)
+}
+
+fn parse_path(path: &str) -> Result<Path, String> {
+    let path = path.trim();
+    if path == "." {
+        return Ok(vec![]);
+    }
+    if path.is_empty() || path.split('.').any(|segment| segment.is_empty()) {
+        return Err(format!("Invalid path '{}'", path));
+    }
+    Ok(path.split('.').map(str::to_owned).collect())
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut rest = src;
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+        }
+        rest = &rest[start..];
+
+        if rest.starts_with("{{{") {
+            let end = rest.find("}}}").ok_or_else(|| "Unclosed '{{{' tag".to_owned())?;
+            tokens.push(Token::Raw(rest[3..end].trim()));
+            rest = &rest[end + 3..];
+        } else {
+            let end = rest.find("}}").ok_or_else(|| "Unclosed '{{' tag".to_owned())?;
+            tokens.push(Token::Tag(rest[2..end].trim()));
+            rest = &rest[end + 2..];
+        }
+    }
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest));
+    }
+    Ok(tokens)
+}
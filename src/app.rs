@@ -1,9 +1,16 @@
-use state::{AppState, DataRef, DataMutRef, TypedKey};
+use state::{AppState, DataRef, DataMutRef, DataEntry, TypedKey};
 use std::rc::Rc;
-use webplatform;
-use uuid::Uuid;
+use webplatform::{self, HtmlNode};
+use view::bind_component;
+use task::TaskHandle;
+use timer::{self, Timers};
+use executor;
+use futures::Future;
+use fetch::{self, Request, Transport};
+use ws::{self, Channel, SocketTransport};
+use rustc_serialize::Decodable;
 
-use {Queryable, Component, View, Node};
+use {Queryable, Component, View, Node, Error, Result, TraceRecord};
 
 /// The main app object instantiated by calling `quasar::init()`
 pub struct QuasarApp<'doc> {
@@ -14,21 +21,25 @@ pub struct QuasarApp<'doc> {
 pub struct AppContext<'doc> {
     app: Rc<AppState<'doc>>,
     view_id: Option<TypedKey>,
+    // The view's node, looked up up front since handlers may hold its binding mutably borrowed
+    node: Option<Rc<HtmlNode<'doc>>>,
 }
 
-pub fn init<'a, 'doc: 'a>() -> QuasarApp<'a> {
+pub fn init() -> QuasarApp<'static> {
     let document = webplatform::init();
-    QuasarApp {
-        app: Rc::new(AppState::new(document)),
-    }
+    let app = Rc::new(AppState::new(document));
+    executor::set_wake_app(&app);
+    QuasarApp { app: app }
 }
 
 impl<'doc> AppContext<'doc> {
     #![doc(hidden)]
     pub fn new(app: Rc<AppState<'doc>>, view_id: Option<TypedKey>) -> AppContext<'doc> {
+        let node = view_id.as_ref().and_then(|view_id| app.binding_node(view_id));
         AppContext {
             app: app,
             view_id: view_id,
+            node: node,
         }
     }
 
@@ -37,13 +48,19 @@ impl<'doc> AppContext<'doc> {
     /// This will flag the view in scope as an observer of this data bucket,
     ///   and any modifications to data at this key will cause this view to be re-rendered.
     pub fn data<T: 'static>(&self, key: &str) -> Option<DataRef<T>> {
-        let type_id = TypedKey::new::<T>(&key);
-        if let Some(ref view_id) = self.view_id {
-            self.app.add_observer(type_id, view_id.clone());
-        }
+        self.observe::<T>(key);
         self.app.data(key)
     }
 
+    /// Get app data for a specific key, without panicking on a borrow conflict
+    ///
+    /// Like `data`, but returns an `Error` if there is no data at this key
+    ///   or if it is currently mutably borrowed.
+    pub fn try_data<T: 'static>(&self, key: &str) -> Result<DataRef<T>> {
+        self.observe::<T>(key);
+        self.app.try_data(key)
+    }
+
     /// Get app data for a specific key
     ///
     /// This will flag the view in scope as an observer of this data bucket,
@@ -51,21 +68,285 @@ impl<'doc> AppContext<'doc> {
     /// It will also cause all observers of this view to be re-rendered after processing
     ///   of the current event is finished.
     pub fn data_mut<T: 'static>(&mut self, key: &str) -> Option<DataMutRef<T>> {
-        let type_id = TypedKey::new::<T>(&key);
+        self.observe::<T>(key);
+        self.app.data_mut(key)
+    }
+
+    /// Get mutable app data for a specific key, without panicking on a borrow conflict
+    ///
+    /// Like `data_mut`, but returns an `Error` if there is no data at this key
+    ///   or if it is currently borrowed.
+    pub fn try_data_mut<T: 'static>(&mut self, key: &str) -> Result<DataMutRef<T>> {
+        self.observe::<T>(key);
+        self.app.try_data_mut(key)
+    }
+
+    /// Set app data for a specific key
+    ///
+    /// This will cause all observers of this data bucket to be re-rendered after processing
+    ///   of the current event is finished.
+    pub fn data_set<T: 'static>(&mut self, key: &str, data: T) {
+        self.app.data_set(key, data)
+    }
+
+    /// Set app data for a specific key, without panicking on a borrow conflict
+    ///
+    /// Like `data_set`, but returns an `Error` if the data at this key is currently borrowed.
+    pub fn try_data_set<T: 'static>(&mut self, key: &str, data: T) -> Result<()> {
+        self.app.try_data_set(key, data)
+    }
+
+    /// Remove and return app data for a specific key
+    ///
+    /// This will cause all observers of this data bucket to be re-rendered after processing
+    ///   of the current event is finished.
+    pub fn data_remove<T: 'static>(&mut self, key: &str) -> Option<T> {
+        self.app.data_remove(key)
+    }
+
+    /// Remove and return app data for a specific key, without panicking on a borrow conflict
+    ///
+    /// Like `data_remove`, but returns an `Error` if the data at this key is currently borrowed.
+    pub fn try_data_remove<T: 'static>(&mut self, key: &str) -> Result<Option<T>> {
+        self.app.try_data_remove(key)
+    }
+
+    /// Get the app data entry for a specific key for in-place initialization
+    ///
+    /// This will flag the view in scope as an observer of this data bucket.
+    pub fn data_entry<'a, T: 'static>(&'a mut self, key: &str) -> DataEntry<'a, 'doc, T> {
+        self.observe::<T>(key);
+        self.app.data_entry(key)
+    }
+
+    /// Mutate app data for a specific key with a closure
+    ///
+    /// Returns `None` without calling `f` if there is no data at this key.
+    /// All observers of this data bucket will be re-rendered after processing
+    ///   of the current event is finished, even if `f` leaves the data unchanged.
+    ///   See `data_update_if_changed` to skip those re-renders for `Clone + PartialEq` data.
+    pub fn data_update<T, F, U>(&mut self, key: &str, f: F) -> Option<U>
+        where T: 'static,
+              F: FnOnce(&mut T) -> U
+    {
+        self.observe::<T>(key);
+        self.app.data_update(key, f)
+    }
+
+    /// Mutate app data for a specific key with a closure, without panicking on a borrow conflict
+    ///
+    /// Like `data_update`, but returns an `Error` if there is no data at this key
+    ///   or if it is currently borrowed.
+    pub fn try_data_update<T, F, U>(&mut self, key: &str, f: F) -> Result<U>
+        where T: 'static,
+              F: FnOnce(&mut T) -> U
+    {
+        self.observe::<T>(key);
+        self.app.try_data_update(key, f)
+    }
+
+    /// Mutate app data for a specific key with a closure, only notifying observers on change
+    ///
+    /// The data is compared to a copy taken before calling `f`, and observers of this data
+    ///   bucket are only re-rendered if the two differ. This costs a clone and a comparison
+    ///   per update, which is why `data_update` does not do it.
+    pub fn data_update_if_changed<T, F, U>(&mut self, key: &str, f: F) -> Option<U>
+        where T: 'static + Clone + PartialEq,
+              F: FnOnce(&mut T) -> U
+    {
+        self.observe::<T>(key);
+        self.app.data_update_if_changed(key, f)
+    }
+
+    /// Mutate app data for a specific key with a closure, only notifying observers on change,
+    ///   without panicking on a borrow conflict
+    ///
+    /// Like `data_update_if_changed`, but returns an `Error` if there is no data at this key
+    ///   or if it is currently borrowed.
+    pub fn try_data_update_if_changed<T, F, U>(&mut self, key: &str, f: F) -> Result<U>
+        where T: 'static + Clone + PartialEq,
+              F: FnOnce(&mut T) -> U
+    {
+        self.observe::<T>(key);
+        self.app.try_data_update_if_changed(key, f)
+    }
+
+    /// Call `f` once after `ms` milliseconds
+    ///
+    /// `f` gets an `AppContext` for the same view, and the render queue is processed afterwards
+    ///   so that any changes it makes are rendered. Unbinding the view cancels the timeout.
+    pub fn set_timeout<F>(&self, ms: u32, f: F) -> TaskHandle<'doc>
+        where F: FnOnce(AppContext<'doc>) + 'doc
+    {
+        timer::set_timeout(&self.app, self.view_id.clone(), ms, f)
+    }
+
+    /// Call `f` every `ms` milliseconds until the returned handle is cancelled
+    ///
+    /// Like `set_timeout`, the render queue is processed after each call,
+    ///   and unbinding the view cancels the interval.
+    pub fn set_interval<F>(&self, ms: u32, f: F) -> TaskHandle<'doc>
+        where F: FnMut(AppContext<'doc>) + 'doc
+    {
+        timer::set_interval(&self.app, self.view_id.clone(), ms, f)
+    }
+
+    /// Send `request`, loading its JSON response into app data at `key` as a `FetchState<T>`
+    ///
+    /// The data is `FetchState::Loading` until the response is decoded, and its observers are
+    ///   rerendered when it completes. Unbinding the view before then discards the response.
+    pub fn fetch<T: 'static + Decodable>(&mut self, key: &str, request: Request) -> TaskHandle<'doc> {
+        fetch::fetch::<T>(&self.app, self.view_id.clone(), key, request)
+    }
+
+    /// Open a WebSocket to `url`, applying each JSON message to app data at `key` with `reducer`
+    ///
+    /// Observers of the data are rerendered after each message, and the socket reconnects
+    ///   with backoff if the connection drops. Unbinding the view closes the channel.
+    pub fn connect<M, T, F>(&mut self, url: &str, key: &str, reducer: F) -> Channel<'doc>
+        where M: Decodable,
+              T: 'static + Default,
+              F: Fn(&mut T, M) + 'doc
+    {
+        ws::connect(&self.app, self.view_id.clone(), url, key, reducer)
+    }
+
+    /// Run `future` on the event loop, calling `done` with its result when it completes
+    ///
+    /// `done` gets an `AppContext` for the same view, and the render queue is processed afterwards.
+    /// Unbinding the view drops the future without calling `done`.
+    pub fn spawn<F, C>(&self, future: F, done: C) -> TaskHandle<'doc>
+        where F: Future + 'doc,
+              C: FnOnce(AppContext<'doc>, ::std::result::Result<F::Item, F::Error>) + 'doc
+    {
+        executor::spawn(&self.app, self.view_id.clone(), future, done)
+    }
+
+    /// Create a detached element, to be inserted with e.g. `Node::append_child`
+    pub fn create_element(&self, tag: &str) -> Result<Node<'doc>> {
+        self.app.create_element(tag)
+    }
+
+    /// Query the whole document, even from the context of a view
+    pub fn query_document(&self, el: &str) -> Option<Node<'doc>> {
+        self.app.document.element_query(el).map(|node| {
+            Node::new(self.app.clone(), Rc::new(node))
+        })
+    }
+
+    /// Query the whole document for every element matching `el`, even from the context of a view
+    pub fn query_all_document(&self, el: &str) -> Vec<Node<'doc>> {
+        self.app.document.element_query_all(el).into_iter().map(|node| {
+            Node::new(self.app.clone(), Rc::new(node))
+        }).collect()
+    }
+
+    // The node of the view in scope, or None outside of a view,
+    //   returning an error if the view is no longer bound
+    fn view_node(&self) -> Result<Option<Rc<HtmlNode<'doc>>>> {
+        let view_id = match self.view_id {
+            Some(ref view_id) => view_id,
+            None => return Ok(None),
+        };
+        if !self.app.is_bound(view_id) {
+            return Err(Error::MissingBinding(view_id.key().to_owned()));
+        }
+        self.node.clone()
+            .or_else(|| self.app.binding_node(view_id))
+            .map(Some)
+            .ok_or_else(|| Error::BorrowConflict(format!("View binding for '{}'", view_id.key())))
+    }
+
+    fn observe<T: 'static>(&self, key: &str) {
         if let Some(ref view_id) = self.view_id {
+            let type_id = TypedKey::new::<T>(key);
             self.app.add_observer(type_id, view_id.clone());
         }
-        self.app.data_mut(key)
     }
 }
 
 
 
 impl<'doc> QuasarApp<'doc> {
-    pub fn query_all(&self, el: &str) -> Vec<Node<'doc>> {
-        self.app.document.element_query_all(el).into_iter().map(|node| {
-            Node::new(self.app.clone(), Rc::new(node))
-        }).collect()
+    /// Set a callback to receive structured records of events, rendering and data observation
+    ///
+    /// Unlike quasar's debug logging, the tracer is also called in release builds.
+    pub fn set_tracer<F>(&mut self, tracer: F)
+        where F: Fn(&TraceRecord) + 'static
+    {
+        self.app.set_tracer(Some(Rc::new(tracer)))
+    }
+
+    /// Remove the callback set by `set_tracer`
+    pub fn clear_tracer(&mut self) {
+        self.app.set_tracer(None)
+    }
+
+    /// Call `f` once after `ms` milliseconds, then process the render queue
+    pub fn set_timeout<F>(&self, ms: u32, f: F) -> TaskHandle<'doc>
+        where F: FnOnce(AppContext<'doc>) + 'doc
+    {
+        timer::set_timeout(&self.app, None, ms, f)
+    }
+
+    /// Call `f` every `ms` milliseconds until the returned handle is cancelled,
+    ///   processing the render queue after each call
+    pub fn set_interval<F>(&self, ms: u32, f: F) -> TaskHandle<'doc>
+        where F: FnMut(AppContext<'doc>) + 'doc
+    {
+        timer::set_interval(&self.app, None, ms, f)
+    }
+
+    /// Replace the timers used by `set_timeout` and `set_interval`, e.g. with `ManualTimers` in tests
+    pub fn set_timers<T: Timers<'doc> + 'doc>(&mut self, timers: Rc<T>) {
+        self.app.set_timers(timers)
+    }
+
+    /// Send `request`, loading its JSON response into app data at `key` (see `AppContext::fetch`)
+    pub fn fetch<T: 'static + Decodable>(&mut self, key: &str, request: Request) -> TaskHandle<'doc> {
+        fetch::fetch::<T>(&self.app, None, key, request)
+    }
+
+    /// Open a WebSocket applying JSON messages to app data at `key` (see `AppContext::connect`)
+    pub fn connect<M, T, F>(&mut self, url: &str, key: &str, reducer: F) -> Channel<'doc>
+        where M: Decodable,
+              T: 'static + Default,
+              F: Fn(&mut T, M) + 'doc
+    {
+        ws::connect(&self.app, None, url, key, reducer)
+    }
+
+    /// Run `future` on the event loop, calling `done` with its result (see `AppContext::spawn`)
+    pub fn spawn<F, C>(&self, future: F, done: C) -> TaskHandle<'doc>
+        where F: Future + 'doc,
+              C: FnOnce(AppContext<'doc>, ::std::result::Result<F::Item, F::Error>) + 'doc
+    {
+        executor::spawn(&self.app, None, future, done)
+    }
+
+    /// Poll spawned futures that have been woken, processing the render queue after each completes
+    ///
+    /// In the browser, futures are polled after each event quasar handles; without an event loop
+    ///   (e.g. in tests), call this to drive them. Returns `true` once every spawned future has completed.
+    pub fn run_tasks(&self) -> bool {
+        let executor = self.app.executor();
+        executor.run();
+        executor.is_idle()
+    }
+
+    /// Replace the transport used to send requests for `fetch`, e.g. with a `StubTransport` in tests
+    pub fn set_transport<T: Transport<'doc> + 'doc>(&mut self, transport: Rc<T>) {
+        self.app.set_transport(transport)
+    }
+
+    /// Replace the transport used to open sockets for `connect`, e.g. with an `EchoTransport` in tests
+    pub fn set_socket_transport<T: SocketTransport<'doc> + 'doc>(&mut self, transport: Rc<T>) {
+        self.app.set_socket_transport(transport)
+    }
+
+    /// Create a detached element, to be inserted with e.g. `Node::append_child`
+    pub fn create_element(&self, tag: &str) -> Result<Node<'doc>> {
+        self.app.create_element(tag)
     }
 
     /// Get app data for a specific key
@@ -73,15 +354,84 @@ impl<'doc> QuasarApp<'doc> {
         self.app.data(key)
     }
 
+    /// Get app data for a specific key, returning an `Error` if it is missing or mutably borrowed
+    pub fn try_data<T: 'static>(&self, key: &str) -> Result<DataRef<T>> {
+        self.app.try_data(key)
+    }
+
     /// Get app data for a specific key
     pub fn data_mut<T: 'static>(&mut self, key: &str) -> Option<DataMutRef<T>> {
         self.app.data_mut(key)
     }
 
-    /// Get app data for a specific key
+    /// Get mutable app data for a specific key, returning an `Error` if it is missing or borrowed
+    pub fn try_data_mut<T: 'static>(&mut self, key: &str) -> Result<DataMutRef<T>> {
+        self.app.try_data_mut(key)
+    }
+
+    /// Set app data for a specific key
     pub fn data_set<T: 'static>(&mut self, key: &str, data: T) {
         self.app.data_set(key, data)
     }
+
+    /// Set app data for a specific key, returning an `Error` if it is borrowed
+    pub fn try_data_set<T: 'static>(&mut self, key: &str, data: T) -> Result<()> {
+        self.app.try_data_set(key, data)
+    }
+
+    /// Remove and return app data for a specific key
+    pub fn data_remove<T: 'static>(&mut self, key: &str) -> Option<T> {
+        self.app.data_remove(key)
+    }
+
+    /// Remove and return app data for a specific key, returning an `Error` if it is borrowed
+    pub fn try_data_remove<T: 'static>(&mut self, key: &str) -> Result<Option<T>> {
+        self.app.try_data_remove(key)
+    }
+
+    /// Get the app data entry for a specific key for in-place initialization
+    pub fn data_entry<'a, T: 'static>(&'a mut self, key: &str) -> DataEntry<'a, 'doc, T> {
+        self.app.data_entry(key)
+    }
+
+    /// Mutate app data for a specific key with a closure
+    ///
+    /// Returns `None` without calling `f` if there is no data at this key.
+    /// Observers of this data bucket are always re-rendered, even if `f` leaves the data
+    ///   unchanged (see `data_update_if_changed`).
+    pub fn data_update<T, F, U>(&mut self, key: &str, f: F) -> Option<U>
+        where T: 'static,
+              F: FnOnce(&mut T) -> U
+    {
+        self.app.data_update(key, f)
+    }
+
+    /// Mutate app data for a specific key with a closure, returning an `Error` if it is missing or borrowed
+    pub fn try_data_update<T, F, U>(&mut self, key: &str, f: F) -> Result<U>
+        where T: 'static,
+              F: FnOnce(&mut T) -> U
+    {
+        self.app.try_data_update(key, f)
+    }
+
+    /// Mutate app data for a specific key with a closure, only notifying observers on change
+    ///
+    /// Like `data_update`, but the data is compared to a copy taken before calling `f`,
+    ///   and observers are only re-rendered if the two differ.
+    pub fn data_update_if_changed<T, F, U>(&mut self, key: &str, f: F) -> Option<U>
+        where T: 'static + Clone + PartialEq,
+              F: FnOnce(&mut T) -> U
+    {
+        self.app.data_update_if_changed(key, f)
+    }
+
+    /// Like `data_update_if_changed`, returning an `Error` if the data is missing or borrowed
+    pub fn try_data_update_if_changed<T, F, U>(&mut self, key: &str, f: F) -> Result<U>
+        where T: 'static + Clone + PartialEq,
+              F: FnOnce(&mut T) -> U
+    {
+        self.app.try_data_update_if_changed(key, f)
+    }
 }
 
 
@@ -93,42 +443,48 @@ impl<'doc> Queryable<'doc> for QuasarApp<'doc> {
         })
     }
 
-    fn bind<R: 'static + Component>(&self, el: &str, component: R) {
-        let node = self.app.document.element_query(el).expect("querySelector found no results");
-        let rc_node = Rc::new(node);
-
-        let render_node = Node::new(self.app.clone(), rc_node.clone());
-        let key = Uuid::new_v4().to_string();
-
-        let app_context = AppContext::new(self.app.clone(), Some(TypedKey::new::<R>(&key)));
-        rc_node.html_patch(&component.render(&render_node, &app_context));
+    fn query_all(&self, el: &str) -> Vec<Node<'doc>> {
+        self.app.document.element_query_all(el).into_iter().map(|node| {
+            Node::new(self.app.clone(), Rc::new(node))
+        }).collect()
+    }
 
-        let binding = self.app.insert_binding(&key, component, rc_node.clone());
-        let view: View<R> = View::new(self.app.clone(), rc_node, key, binding);
-        Component::onload(&view);
+    fn try_bind<R: 'static + Component>(&self, el: &str, component: R) -> Result<View<'doc, R>> {
+        let node = self.app.document.element_query(el).ok_or_else(|| Error::ElementNotFound(el.to_owned()))?;
+        bind_component(&self.app, node, component)
     }
 }
 
+/// Queries from the context of a view only search within that view's node
+///
+/// Once the view is unbound (e.g. in a handler that outlived it), queries match nothing
+///   and `try_bind` returns `Error::MissingBinding`.
 impl<'doc> Queryable<'doc> for AppContext<'doc> {
     type Q = Node<'doc>;
     fn query(&self, el: &str) -> Option<Node<'doc>> {
-        self.app.document.element_query(el).map(|node| {
-            Node::new(self.app.clone(), Rc::new(node))
-        })
+        let node = match self.view_node() {
+            Ok(Some(view_node)) => view_node.element_query(el),
+            Ok(None) => self.app.document.element_query(el),
+            Err(_) => None,
+        };
+        node.map(|node| Node::new(self.app.clone(), Rc::new(node)))
     }
 
-    fn bind<R: 'static + Component>(&self, el: &str, component: R) {
-        let node = self.app.document.element_query(el).expect("querySelector found no results");
-        let rc_node = Rc::new(node);
-
-        let render_node = Node::new(self.app.clone(), rc_node.clone());
-        let key = Uuid::new_v4().to_string();
-
-        let app_context = AppContext::new(self.app.clone(), Some(TypedKey::new::<R>(&key)));
-        rc_node.html_patch(&component.render(&render_node, &app_context));
+    fn query_all(&self, el: &str) -> Vec<Node<'doc>> {
+        let nodes = match self.view_node() {
+            Ok(Some(view_node)) => view_node.element_query_all(el),
+            Ok(None) => self.app.document.element_query_all(el),
+            Err(_) => vec![],
+        };
+        nodes.into_iter().map(|node| Node::new(self.app.clone(), Rc::new(node))).collect()
+    }
 
-        let binding = self.app.insert_binding(&key, component, rc_node.clone());
-        let view: View<R> = View::new(self.app.clone(), rc_node, key, binding);
-        Component::onload(&view);
+    fn try_bind<R: 'static + Component>(&self, el: &str, component: R) -> Result<View<'doc, R>> {
+        let node = match self.view_node()? {
+            Some(view_node) => view_node.element_query(el),
+            None => self.app.document.element_query(el),
+        };
+        let node = node.ok_or_else(|| Error::ElementNotFound(el.to_owned()))?;
+        bind_component(&self.app, node, component)
     }
 }
\ No newline at end of file
@@ -1,6 +1,14 @@
+use std::any::Any;
 use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use downcast_rs::Downcast;
-use {AppContext, Node};
+use {AppContext, Node, Result};
+
+#[cfg(feature = "mustache")]
+mod runtime;
+#[cfg(feature = "mustache")]
+pub use self::runtime::{RuntimeComponent, RuntimeData};
 
 pub type Properties = HashMap<&'static str, String>;
 
@@ -14,15 +22,49 @@ pub trait Renderable: Downcast {
     ///
     /// Additionally, it is possible to attach additional handlers directly to the node
     /// making it possible to build templating that adds handlers automatically
-    fn render(&self, node: &Node, app: &AppContext) -> String;
+    ///
+    /// The output is inserted into the DOM as-is, so any text interpolated into it must be
+    /// escaped (see `html::escape` and `SafeHtml`).
+    ///
+    /// Failures (e.g. a template engine rejecting the component data) should be returned
+    /// as `Error::Render` rather than panicking, so that binding and rerendering can report them.
+    fn render(&self, node: &Node, app: &AppContext) -> Result<String>;
 }
 
 impl_downcast!(Renderable);
 
-impl<T> Renderable for T
-    where T: ::std::fmt::Display + 'static
-{
-    fn render<'doc>(&self, _node: &Node, _app: &AppContext) -> String {
-        self.to_string()
+/// Hash a value for use as `Component::fingerprint`
+pub fn fingerprint<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A copy of component data for use as `Component::snapshot`
+pub struct Snapshot {
+    value: Box<Any>,
+    // Compares values of the snapshotted type, which are never equal to values of another type
+    eq: fn(&Any, &Any) -> bool,
+}
+
+impl Snapshot {
+    pub fn of<T: 'static + Clone + PartialEq>(value: &T) -> Snapshot {
+        Snapshot {
+            value: Box::new(value.clone()),
+            eq: eq_as::<T>,
+        }
+    }
+}
+
+impl PartialEq for Snapshot {
+    fn eq(&self, other: &Snapshot) -> bool {
+        (self.eq)(&*self.value, &*other.value)
+    }
+}
+
+fn eq_as<T: 'static + PartialEq>(a: &Any, b: &Any) -> bool {
+    match (a.downcast_ref::<T>(), b.downcast_ref::<T>()) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
     }
 }
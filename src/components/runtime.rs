@@ -0,0 +1,210 @@
+use std::cell::RefCell;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use mustache::{self, encoder, Data};
+use rustc_serialize::Encodable;
+use html;
+
+use {AppContext, Component, Error, HasBind, Node, Queryable, Renderable, Result, View};
+
+/// Behavior of a `RuntimeComponent`, implemented by the type of its data
+pub trait RuntimeData: 'static + Encodable + Sized {
+    /// Called once after the component is first bound and rendered (see `Component::onload`)
+    fn onload(_view: &View<RuntimeComponent<Self>>) {}
+}
+
+// A child component waiting to be bound in place of the mustache partial it was registered for
+trait Child<D: RuntimeData> {
+    fn bind_child<'doc>(self: Box<Self>, view: &View<'doc, RuntimeComponent<D>>, el: &str) -> Result<()>;
+}
+
+impl<D: RuntimeData, C: 'static + Component> Child<D> for C {
+    fn bind_child<'doc>(self: Box<Self>, view: &View<'doc, RuntimeComponent<D>>, el: &str) -> Result<()> {
+        view.try_bind(el, *self).map(|_| ())
+    }
+}
+
+/// A component rendering its data with a mustache template compiled at runtime
+///
+/// The data is available as the template context, with the node properties named by `props`
+///   available as `props.*`. A mustache partial (`{{> name}}`) naming a child component registered
+///   with `child` is rendered as a placeholder, into which the child is bound when this component
+///   is bound, and rerendered whenever this component rerenders. Other partials are left to mustache.
+///
+/// Each child component is bound once, into the first of its placeholders, so its partial
+///   should appear once in the template, and not within a section repeated for each item.
+///
+/// ```ignore
+/// impl RuntimeData for CounterData {
+///     fn onload(view: &View<RuntimeComponent<Self>>) {
+///         view.on_each(EventType::Click, "button", |mut evt| evt.binding.data_mut().count += 1);
+///     }
+/// }
+///
+/// let counter = RuntimeComponent::new(CounterData::default(), "<p>{{count}}</p><button>+1</button>")?;
+/// ```
+pub struct RuntimeComponent<D: RuntimeData> {
+    pub data: D,
+    pub template: mustache::Template,
+    pub props: Vec<&'static str>,
+    source: String,
+    children: RefCell<Vec<(String, Box<Child<D>>)>>,
+}
+
+impl<D: RuntimeData> RuntimeComponent<D> {
+    /// Compile `template` for rendering `data`, returning `Error::Template` if it is malformed
+    pub fn new(data: D, template: &str) -> Result<RuntimeComponent<D>> {
+        let compiled = mustache::compile_str(template).map_err(|err| Error::Template(err.to_string()))?;
+        Ok(RuntimeComponent {
+            data: data,
+            template: compiled,
+            props: vec![],
+            source: template.to_owned(),
+            children: RefCell::new(vec![]),
+        })
+    }
+
+    /// Set the names of the node properties available to the template as `props.*`
+    pub fn with_props(mut self, props: &[&'static str]) -> RuntimeComponent<D> {
+        self.props = props.to_vec();
+        self
+    }
+
+    /// Register the child component rendered for the partial `{{> name}}`
+    pub fn child<C: 'static + Component>(mut self, name: &str, component: C) -> RuntimeComponent<D> {
+        match partial_count(&self.source, name) {
+            0 => warn!(target: "quasar::render", "Template has no {{{{> {}}}}} partial for child component '{}'", name, name),
+            1 => (),
+            n => warn!(target: "quasar::render", "Child component '{}' is only bound into the first of {} {{{{> {}}}}} partials", name, n, name),
+        }
+        self.children.borrow_mut().push((name.to_owned(), Box::new(component)));
+
+        let names: Vec<String> = self.children.borrow().iter().map(|&(ref name, _)| name.clone()).collect();
+        // The source already compiled, and placeholders only replace whole partial tags with markup
+        self.template = mustache::compile_str(&child_placeholders(&self.source, &names))
+            .expect("template with child placeholders failed to compile");
+        self
+    }
+}
+
+impl<D: RuntimeData> Deref for RuntimeComponent<D> {
+    type Target = D;
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
+impl<D: RuntimeData> DerefMut for RuntimeComponent<D> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.data
+    }
+}
+
+impl<D: RuntimeData> Renderable for RuntimeComponent<D> {
+    fn render(&self, node: &Node, _app: &AppContext) -> Result<String> {
+        let mut data = encoder::encode(&self.data)
+            .map_err(|err| Error::Render(format!("Failed to encode component data: {}", err)))?;
+
+        match data {
+            Data::Map(ref mut map) => {
+                let props = node.get_properties(&self.props);
+                let mustache_props = props.into_iter()
+                    .map(|(k, v)| (k.to_string(), Data::StrVal(v)))
+                    .collect();
+                map.insert("props".to_string(), Data::Map(mustache_props));
+            }
+            _ => return Err(Error::Render("Component data must encode as a map".to_owned())),
+        }
+
+        let mut output = Vec::new();
+        self.template.render_data(&mut output, &data).map_err(|err| Error::Render(err.to_string()))?;
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+}
+
+impl<D: RuntimeData> Component for RuntimeComponent<D> {
+    fn onload(view: &View<Self>) {
+        let children = mem::replace(&mut *view.data().children.borrow_mut(), vec![]);
+        for (name, child) in children {
+            if let Err(err) = child.bind_child(view, &html::child_selector(&name)) {
+                warn!(target: "quasar::render", "Failed to bind child component '{}': {}", name, err);
+            }
+        }
+        D::onload(view);
+    }
+
+    fn props(&self) -> &[&'static str] {
+        &self.props
+    }
+}
+
+// Replace `{{> name}}` partial tags naming one of `children` with placeholder elements for them
+fn child_placeholders(template: &str, children: &[String]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some((start, end, name)) = next_partial(rest) {
+        out.push_str(&rest[..start]);
+        if children.iter().any(|child| child == name) {
+            out.push_str(html::child(name).as_str());
+        } else {
+            out.push_str(&rest[start..end]);
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+// How many `{{> name}}` partial tags `template` has
+fn partial_count(template: &str, name: &str) -> usize {
+    let mut count = 0;
+    let mut rest = template;
+    while let Some((_, end, partial)) = next_partial(rest) {
+        if partial == name {
+            count += 1;
+        }
+        rest = &rest[end..];
+    }
+    count
+}
+
+// The start and end of the first partial tag in `template`, along with the partial's name
+fn next_partial(template: &str) -> Option<(usize, usize, &str)> {
+    let start = template.find("{{>")?;
+    let end = start + template[start..].find("}}")? + 2;
+    Some((start, end, template[start + 3..end - 2].trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn replaces_only_registered_children() {
+        let out = child_placeholders("<div>{{> counter}}</div>{{>footer}}", &names(&["counter"]));
+        assert_eq!(out, format!("<div>{}</div>{{{{>footer}}}}", html::child("counter")));
+    }
+
+    #[test]
+    fn trims_partial_names() {
+        let out = child_placeholders("{{>  counter }}{{> hello}}", &names(&["counter", "hello"]));
+        assert_eq!(out, format!("{}{}", html::child("counter"), html::child("hello")));
+    }
+
+    #[test]
+    fn leaves_unclosed_tags() {
+        assert_eq!(child_placeholders("<p>{{> counter</p>", &names(&["counter"])), "<p>{{> counter</p>");
+    }
+
+    #[test]
+    fn counts_partials_by_name() {
+        let src = "{{#items}}{{> row}}{{/items}}{{> row }}{{> footer}}";
+        assert_eq!(partial_count(src, "row"), 2);
+        assert_eq!(partial_count(src, "footer"), 1);
+        assert_eq!(partial_count(src, "counter"), 0);
+    }
+}
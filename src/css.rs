@@ -0,0 +1,330 @@
+//! Component-scoped stylesheets
+//!
+//! Each component type with `Component::styles` gets a scope attribute, e.g. `q-s-1a2b3c4d`,
+//!   which is added to every element of its rendered output, but not to the content of
+//!   child views within it. The stylesheet is injected into `<head>` once per type, with
+//!   selectors rewritten to only match those elements: `.todo-list .item` becomes
+//!   `.todo-list .item[q-s-1a2b3c4d]`.
+
+use std::any::TypeId;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+use state::AppState;
+use html::CHILD_ATTR;
+
+use Component;
+
+/// The scope attribute added to the elements rendered by components of type `R`
+pub fn scope_attr<R: 'static>() -> String {
+    let mut hasher = DefaultHasher::new();
+    TypeId::of::<R>().hash(&mut hasher);
+    format!("q-s-{:08x}", hasher.finish() as u32)
+}
+
+/// Inject the scoped stylesheet of `R` into `<head>`, unless already injected
+pub fn inject_styles<'doc, R: 'static + Component>(app: &Rc<AppState<'doc>>) {
+    let styles = match R::styles() {
+        Some(styles) => styles,
+        None => return,
+    };
+    if !app.add_styled(TypeId::of::<R>()) {
+        return;
+    }
+    match app.document.element_query("head") {
+        Some(head) => {
+            let attr = scope_attr::<R>();
+            head.html_append(&format!("<style {}>{}</style>", attr, scope_css(styles, &attr)));
+        }
+        None => warn!(target: "quasar::render", "No <head> element for component styles"),
+    }
+}
+
+/// Add `attr` to the start tag of every element in the rendered markup
+///
+/// The content of child view placeholders (see `html::child`) is left as it is, as are
+///   comments and the content of raw text elements like `<script>`.
+pub fn scope_markup(html: &str, attr: &str) -> String {
+    let mut out = String::with_capacity(html.len() + html.len() / 4);
+    let mut rest = html;
+
+    while let Some(lt) = rest.find('<') {
+        out.push_str(&rest[..lt]);
+        rest = &rest[lt..];
+
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map(|end| end + 3).unwrap_or(rest.len());
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+        if rest.starts_with("</") || rest.starts_with("<!") {
+            let end = tag_end(rest);
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+        if !rest[1..].starts_with(|c: char| c.is_alphabetic()) {
+            // A stray '<' in text
+            out.push('<');
+            rest = &rest[1..];
+            continue;
+        }
+        let name_len = rest[1..]
+            .find(|c: char| !(c.is_alphanumeric() || c == '-'))
+            .unwrap_or(rest.len() - 1);
+
+        let name = rest[1..1 + name_len].to_ascii_lowercase();
+        let end = tag_end(rest);
+        let tag = &rest[..end];
+        out.push_str(&tag[..1 + name_len]);
+        out.push(' ');
+        out.push_str(attr);
+        out.push_str(&tag[1 + name_len..]);
+        rest = &rest[end..];
+
+        if tag.ends_with("/>") {
+            continue;
+        }
+        let raw_text = ["script", "style", "textarea", "title"].contains(&&*name);
+        if raw_text || has_attribute(&tag[1 + name_len..], CHILD_ATTR) {
+            let close = closing_tag(rest, &name, !raw_text);
+            out.push_str(&rest[..close]);
+            rest = &rest[close..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// Position just after the '>' ending the tag at the start of `src`, skipping quoted values
+fn tag_end(src: &str) -> usize {
+    let mut quote = None;
+    for (i, c) in src.char_indices().skip(1) {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '>') => return i + 1,
+            _ => (),
+        }
+    }
+    src.len()
+}
+
+// Whether the attributes of a start tag include `name`
+fn has_attribute(attrs: &str, name: &str) -> bool {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (i, c) in attrs.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, _) if prev.is_whitespace() && attrs[i..].starts_with(name) => {
+                let after = attrs[i + name.len()..].chars().next();
+                if after.map_or(true, |c| c == '=' || c == '/' || c == '>' || c.is_whitespace()) {
+                    return true;
+                }
+            }
+            _ => (),
+        }
+        prev = c;
+    }
+    false
+}
+
+// Position of the end tag closing a `name` element whose content starts `src`,
+//   counting nested elements of the same name unless the content is raw text
+fn closing_tag(src: &str, name: &str, nested: bool) -> usize {
+    // ASCII lowercasing keeps byte offsets the same in `lower` and `src`
+    let lower = src.to_ascii_lowercase();
+    let open = format!("<{}", name);
+    let close = format!("</{}", name);
+    let mut depth = 0;
+    let mut pos = 0;
+    while let Some(lt) = lower[pos..].find('<') {
+        let at = pos + lt;
+        let rest = &lower[at..];
+        let boundary = |len: usize| rest[len..].starts_with(|c: char| c == '>' || c == '/' || c.is_whitespace());
+        if rest.starts_with(&close) && boundary(close.len()) {
+            if depth == 0 {
+                return at;
+            }
+            depth -= 1;
+        } else if nested && rest.starts_with(&open) && boundary(open.len()) &&
+                  !src[at..at + tag_end(&src[at..])].ends_with("/>") {
+            depth += 1;
+        }
+        pos = at + 1;
+    }
+    src.len()
+}
+
+/// Rewrite the selectors of a stylesheet to only match elements with (or within elements with) `attr`
+///
+/// Rules within `@media` and `@supports` are scoped too, while other at-rules
+///   (e.g. `@keyframes` or `@font-face`) are left as they are.
+pub fn scope_css(css: &str, attr: &str) -> String {
+    let mut out = String::with_capacity(css.len() * 2);
+    let mut rest = css;
+
+    loop {
+        rest = skip_space_and_comments(rest);
+        if rest.is_empty() {
+            return out;
+        }
+
+        // A statement like `@import ...;` has no block
+        let open = rest.find('{');
+        let semi = rest.find(';');
+        if let Some(semi) = semi {
+            if rest.starts_with('@') && open.map(|open| semi < open).unwrap_or(true) {
+                out.push_str(&rest[..semi + 1]);
+                rest = &rest[semi + 1..];
+                continue;
+            }
+        }
+
+        let open = match open {
+            Some(open) => open,
+            None => {
+                // Trailing garbage, which browsers would ignore anyway
+                out.push_str(rest);
+                return out;
+            }
+        };
+        let prelude = rest[..open].trim();
+        let close = matching_brace(rest, open);
+        let block = &rest[open + 1..close];
+
+        if prelude.starts_with('@') {
+            if prelude.starts_with("@media") || prelude.starts_with("@supports") {
+                out.push_str(&format!("{} {{{}}}", prelude, scope_css(block, attr)));
+            } else {
+                out.push_str(&format!("{} {{{}}}", prelude, block));
+            }
+        } else {
+            let selectors: Vec<String> = split_selectors(prelude)
+                .into_iter()
+                .map(|selector| scope_selector(selector, attr))
+                .collect();
+            out.push_str(&format!("{} {{{}}}", selectors.join(", "), block));
+        }
+        out.push('\n');
+        rest = &rest[(close + 1).min(rest.len())..];
+    }
+}
+
+// Only match the element targeted by `selector` if it has `attr`
+fn scope_selector(selector: &str, attr: &str) -> String {
+    let mut last_start = 0;
+    while let Some(i) = top_level_position(&selector[last_start..],
+                                           |c| c.is_whitespace() || c == '>' || c == '+' || c == '~') {
+        last_start += i + 1;
+    }
+    // Attribute selectors may not follow a pseudo-element
+    let insert_at = selector[last_start..].find("::").map_or(selector.len(), |i| last_start + i);
+    format!("{}[{}]{}", &selector[..insert_at], attr, &selector[insert_at..])
+}
+
+fn split_selectors(prelude: &str) -> Vec<&str> {
+    let mut selectors = vec![];
+    let mut rest = prelude;
+    while let Some(comma) = top_level_position(rest, |c| c == ',') {
+        selectors.push(rest[..comma].trim());
+        rest = &rest[comma + 1..];
+    }
+    selectors.push(rest.trim());
+    selectors.into_iter().filter(|selector| !selector.is_empty()).collect()
+}
+
+// Position of the first character matching `pred` outside of parentheses, brackets and quotes
+fn top_level_position<P: Fn(char) -> bool>(src: &str, pred: P) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote = None;
+    for (i, c) in src.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '(') | (None, '[') => depth += 1,
+            (None, ')') | (None, ']') => depth -= 1,
+            (None, c) if depth == 0 && pred(c) => return Some(i),
+            _ => (),
+        }
+    }
+    None
+}
+
+// Position of the '}' closing the '{' at `open`, or the end of `src` if unclosed
+fn matching_brace(src: &str, open: usize) -> usize {
+    let mut depth = 0;
+    for (i, c) in src[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return open + i;
+                }
+            }
+            _ => (),
+        }
+    }
+    src.len()
+}
+
+fn skip_space_and_comments(mut src: &str) -> &str {
+    loop {
+        src = src.trim_left();
+        if !src.starts_with("/*") {
+            return src;
+        }
+        src = src.find("*/").map(|end| &src[end + 2..]).unwrap_or("");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes_the_target_of_each_selector() {
+        assert_eq!(scope_css(".item { color: red }", "q-s"), ".item[q-s] { color: red }\n");
+        assert_eq!(scope_css(".list > li a:hover, p{}", "q-s"), ".list > li a:hover[q-s], p[q-s] {}\n");
+        assert_eq!(scope_css("a[title='x y']::before {}", "q-s"), "a[title='x y'][q-s]::before {}\n");
+        assert_eq!(scope_css("li:not(.a .b) {}", "q-s"), "li:not(.a .b)[q-s] {}\n");
+    }
+
+    #[test]
+    fn scopes_nested_rules_but_not_other_at_rules() {
+        let css = "@import 'x.css'; @media (min-width: 10px) { p { margin: 0 } } @keyframes k { from { top: 0 } }";
+        assert_eq!(scope_css(css, "q-s"),
+                   "@import 'x.css';@media (min-width: 10px) {p[q-s] { margin: 0 }\n}\n@keyframes k { from { top: 0 } }\n");
+    }
+
+    #[test]
+    fn scopes_every_rendered_element() {
+        assert_eq!(scope_markup("<ul class=\"a>b\"><li>x</li><br/></ul>", "q-s"),
+                   "<ul q-s class=\"a>b\"><li q-s>x</li><br q-s/></ul>");
+        assert_eq!(scope_markup("1 < 2 <b><!-- <i> --></b>", "q-s"), "1 < 2 <b q-s><!-- <i> --></b>");
+    }
+
+    #[test]
+    fn leaves_raw_text_alone() {
+        assert_eq!(scope_markup("<script>if (a<b) {}</script><p></p>", "q-s"),
+                   "<script q-s>if (a<b) {}</script><p q-s></p>");
+    }
+
+    #[test]
+    fn stops_at_child_placeholders() {
+        let html = "<div q-child=\"c\"><div><p>child</p></div></div><p>after</p>";
+        assert_eq!(scope_markup(html, "q-s"),
+                   "<div q-s q-child=\"c\"><div><p>child</p></div></div><p q-s>after</p>");
+        assert_eq!(scope_markup("<div data-q-child=\"c\"><p></p></div>", "q-s"),
+                   "<div q-s data-q-child=\"c\"><p q-s></p></div>");
+        assert_eq!(scope_markup("<div q-child=\"c\">İİİİİİ<div>é</div></div><p>after</p>", "q-s"),
+                   "<div q-s q-child=\"c\">İİİİİİ<div>é</div></div><p q-s>after</p>");
+    }
+}
@@ -0,0 +1,427 @@
+use std::fmt;
+use std::rc::Rc;
+use webplatform::HtmlNode;
+use platform;
+
+/// A value for an inline style property (see `Node::set_style`)
+pub trait StyleValue {
+    fn css_value(&self) -> String;
+}
+
+impl<'a> StyleValue for &'a str {
+    fn css_value(&self) -> String {
+        (*self).to_owned()
+    }
+}
+
+impl StyleValue for String {
+    fn css_value(&self) -> String {
+        self.clone()
+    }
+}
+
+/// A length in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Px(pub f64);
+
+/// A length relative to the font size
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Em(pub f64);
+
+/// A percentage
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Percent(pub f64);
+
+impl fmt::Display for Px {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}px", self.0)
+    }
+}
+
+impl fmt::Display for Em {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}em", self.0)
+    }
+}
+
+impl fmt::Display for Percent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}%", self.0)
+    }
+}
+
+impl StyleValue for Px {
+    fn css_value(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl StyleValue for Em {
+    fn css_value(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl StyleValue for Percent {
+    fn css_value(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// A type that DOM properties can be read as and written from (see `Node::get_prop`)
+pub trait PropValue: Sized {
+    fn get_prop(node: &HtmlNode, prop: &str) -> Self;
+    fn set_prop(&self, node: &HtmlNode, prop: &str);
+}
+
+impl PropValue for String {
+    fn get_prop(node: &HtmlNode, prop: &str) -> String {
+        node.prop_get_str(prop)
+    }
+
+    fn set_prop(&self, node: &HtmlNode, prop: &str) {
+        node.prop_set_str(prop, self)
+    }
+}
+
+impl PropValue for bool {
+    fn get_prop(node: &HtmlNode, prop: &str) -> bool {
+        node.prop_get_i32(prop) != 0
+    }
+
+    fn set_prop(&self, node: &HtmlNode, prop: &str) {
+        node.prop_set_i32(prop, *self as i32)
+    }
+}
+
+impl PropValue for i32 {
+    fn get_prop(node: &HtmlNode, prop: &str) -> i32 {
+        node.prop_get_i32(prop)
+    }
+
+    fn set_prop(&self, node: &HtmlNode, prop: &str) {
+        node.prop_set_i32(prop, *self)
+    }
+}
+
+impl PropValue for f64 {
+    // Properties that are not numbers read as NaN
+    fn get_prop(node: &HtmlNode, prop: &str) -> f64 {
+        node.prop_get_str(prop).trim().parse().unwrap_or(::std::f64::NAN)
+    }
+
+    // The DOM converts the string back to a number for numeric properties
+    fn set_prop(&self, node: &HtmlNode, prop: &str) {
+        node.prop_set_str(prop, &self.to_string())
+    }
+}
+
+/// Metadata of a file chosen with an `<input type="file">`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileInfo {
+    pub name: String,
+    /// Size in bytes
+    pub size: u64,
+    pub mime_type: String,
+}
+
+// The DOM side of `Node`'s class and style methods
+
+pub fn classes(node: &HtmlNode) -> Vec<String> {
+    parse_classes(&node.prop_get_str("className"))
+}
+
+pub fn has_class(node: &HtmlNode, class: &str) -> bool {
+    classes(node).iter().any(|c| c == class)
+}
+
+pub fn add_class(node: &HtmlNode, class: &str) {
+    let mut classes = classes(node);
+    if insert_class(&mut classes, class) {
+        node.prop_set_str("className", &classes.join(" "));
+    }
+}
+
+pub fn remove_class(node: &HtmlNode, class: &str) {
+    let mut classes = classes(node);
+    if delete_class(&mut classes, class) {
+        node.prop_set_str("className", &classes.join(" "));
+    }
+}
+
+pub fn toggle_class(node: &HtmlNode, class: &str) -> bool {
+    let present = has_class(node, class);
+    if present {
+        remove_class(node, class);
+    } else {
+        add_class(node, class);
+    }
+    !present
+}
+
+// Styles are read from and written back to the `style` attribute, replacing the whole inline style
+fn set_styles(node: &HtmlNode, styles: &[(String, String)]) {
+    node.attr_set_str("style", &format_styles(styles));
+}
+
+pub fn style(node: &HtmlNode, name: &str) -> Option<String> {
+    let name = style_name(name);
+    parse_styles(&node.attr_get_str("style")).into_iter().find(|&(ref n, _)| *n == name).map(|(_, value)| value)
+}
+
+pub fn set_style(node: &HtmlNode, name: &str, value: &str) {
+    let mut styles = parse_styles(&node.attr_get_str("style"));
+    update_style(&mut styles, name, value);
+    set_styles(node, &styles);
+}
+
+pub fn remove_style(node: &HtmlNode, name: &str) {
+    let mut styles = parse_styles(&node.attr_get_str("style"));
+    delete_style(&mut styles, name);
+    set_styles(node, &styles);
+}
+
+// Traversal is built on `parent` and `element_query_all`, so nodes outside the document
+//   (e.g. created but not yet inserted) have no siblings
+
+pub fn children<'doc>(node: &HtmlNode<'doc>) -> Vec<HtmlNode<'doc>> {
+    node.element_query_all(":scope > *")
+}
+
+// The element `offset` positions after `node` among its parent's children
+pub fn sibling<'doc>(node: &HtmlNode<'doc>, offset: isize) -> Option<HtmlNode<'doc>> {
+    let mut siblings = children(&node.parent()?);
+    let pos = siblings.iter().position(|sibling| sibling == node)? as isize + offset;
+    if pos < 0 || pos as usize >= siblings.len() {
+        return None;
+    }
+    Some(siblings.swap_remove(pos as usize))
+}
+
+pub fn matches(node: &HtmlNode, selector: &str) -> bool {
+    platform::matches(node, selector)
+}
+
+// Like `Element.closest`, but walking `parent` since webplatform can't return the node it finds
+pub fn closest<'doc>(node: &Rc<HtmlNode<'doc>>, selector: &str) -> Option<Rc<HtmlNode<'doc>>> {
+    if matches(node, selector) {
+        return Some(node.clone());
+    }
+    let mut current = node.parent();
+    while let Some(ancestor) = current {
+        if matches(&ancestor, selector) {
+            return Some(Rc::new(ancestor));
+        }
+        current = ancestor.parent();
+    }
+    None
+}
+
+// Call `f` with `node` and then each of its ancestors below `root`, returning the first result,
+//   or None if `node` is not within `root`
+pub fn find_within<'doc, T, F>(node: &HtmlNode<'doc>, root: &HtmlNode<'doc>, mut f: F) -> Option<T>
+    where F: FnMut(&HtmlNode<'doc>) -> Option<T>
+{
+    if node == root {
+        return None;
+    }
+    let mut found = f(node);
+    let mut current = node.parent();
+    while let Some(ancestor) = current {
+        if ancestor == *root {
+            return found;
+        }
+        if found.is_none() {
+            found = f(&ancestor);
+        }
+        current = ancestor.parent();
+    }
+    None
+}
+
+// The string manipulation behind the helpers above, kept apart from the DOM for testing
+
+fn parse_classes(class_name: &str) -> Vec<String> {
+    let mut classes: Vec<String> = vec![];
+    for class in class_name.split_whitespace() {
+        insert_class(&mut classes, class);
+    }
+    classes
+}
+
+// Whether `class` was added, i.e. was missing
+fn insert_class(classes: &mut Vec<String>, class: &str) -> bool {
+    if classes.iter().any(|c| c == class) {
+        return false;
+    }
+    classes.push(class.to_owned());
+    true
+}
+
+// Whether `class` was removed, i.e. was present
+fn delete_class(classes: &mut Vec<String>, class: &str) -> bool {
+    let len = classes.len();
+    classes.retain(|c| c != class);
+    classes.len() != len
+}
+
+// Property names are case-insensitive, except for custom properties like `--main-color`
+fn style_name(name: &str) -> String {
+    let name = name.trim();
+    if name.starts_with("--") { name.to_owned() } else { name.to_ascii_lowercase() }
+}
+
+// Inline style declarations as (property, value) pairs, in order
+//
+// Semicolons and colons within quotes or parentheses (e.g. in `url(data:...)`) are part of the value.
+fn parse_styles(style: &str) -> Vec<(String, String)> {
+    let mut styles = vec![];
+    let mut depth = 0;
+    let mut quote = None;
+    let mut start = 0;
+    let mut colon = None;
+    for (i, c) in style.char_indices().chain(Some((style.len(), ';'))) {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, ':') if depth == 0 && colon.is_none() => colon = Some(i),
+            (None, ';') if depth == 0 || i == style.len() => {
+                if let Some(colon) = colon {
+                    let name = style_name(&style[start..colon]);
+                    let value = style[colon + 1..i].trim();
+                    if !name.is_empty() {
+                        update_style(&mut styles, &name, value);
+                    }
+                }
+                start = i + 1;
+                colon = None;
+                depth = 0;
+            }
+            _ => (),
+        }
+    }
+    styles
+}
+
+fn format_styles(styles: &[(String, String)]) -> String {
+    let decls: Vec<String> = styles.iter().map(|&(ref name, ref value)| format!("{}: {}", name, value)).collect();
+    decls.join("; ")
+}
+
+fn update_style(styles: &mut Vec<(String, String)>, name: &str, value: &str) {
+    let name = style_name(name);
+    match styles.iter().position(|&(ref n, _)| *n == name) {
+        Some(pos) => styles[pos].1 = value.to_owned(),
+        None => styles.push((name, value.to_owned())),
+    }
+}
+
+fn delete_style(styles: &mut Vec<(String, String)>, name: &str) {
+    let name = style_name(name);
+    styles.retain(|&(ref n, _)| *n != name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webplatform;
+
+    fn pairs(styles: &[(&str, &str)]) -> Vec<(String, String)> {
+        styles.iter().map(|&(name, value)| (name.to_owned(), value.to_owned())).collect()
+    }
+
+    #[test]
+    fn parses_classes_without_duplicates() {
+        assert_eq!(parse_classes("  a b\ta "), vec!["a", "b"]);
+        assert!(parse_classes("").is_empty());
+    }
+
+    #[test]
+    fn adds_removes_and_toggles_classes() {
+        let mut classes = parse_classes("a b");
+        assert!(!insert_class(&mut classes, "a"));
+        assert!(insert_class(&mut classes, "c"));
+        assert_eq!(classes.join(" "), "a b c");
+        assert!(delete_class(&mut classes, "b"));
+        assert!(!delete_class(&mut classes, "b"));
+        assert_eq!(classes.join(" "), "a c");
+    }
+
+    #[test]
+    fn parses_style_declarations() {
+        assert_eq!(parse_styles("Color: red;; margin : 0 auto ;bogus"),
+                   pairs(&[("color", "red"), ("margin", "0 auto")]));
+        assert_eq!(parse_styles("color: red; COLOR: blue"), pairs(&[("color", "blue")]));
+        assert!(parse_styles("").is_empty());
+    }
+
+    #[test]
+    fn respects_quotes_and_parens_in_values() {
+        let style = "background: url(data:image/png;base64,AA==); content: \"a;b:c\"; font-family: 'x;y'";
+        assert_eq!(parse_styles(style),
+                   pairs(&[("background", "url(data:image/png;base64,AA==)"),
+                           ("content", "\"a;b:c\""),
+                           ("font-family", "'x;y'")]));
+    }
+
+    #[test]
+    fn keeps_custom_property_names() {
+        let mut styles = parse_styles("--Main-Color: red; COLOR: var(--Main-Color)");
+        assert_eq!(styles, pairs(&[("--Main-Color", "red"), ("color", "var(--Main-Color)")]));
+        update_style(&mut styles, "--main-color", "blue");
+        delete_style(&mut styles, "Color");
+        assert_eq!(format_styles(&styles), "--Main-Color: red; --main-color: blue");
+    }
+
+    #[test]
+    fn styles_round_trip_through_the_style_attribute() {
+        let document = webplatform::init();
+        let node = document.element_create("div").unwrap();
+        node.attr_set_str("style", "color: red");
+        set_style(&node, "width", "10px");
+        assert_eq!(node.attr_get_str("style"), "color: red; width: 10px");
+        assert_eq!(style(&node, "WIDTH"), Some("10px".to_owned()));
+        remove_style(&node, "color");
+        assert_eq!(node.attr_get_str("style"), "width: 10px");
+    }
+
+    #[test]
+    fn walks_children_and_siblings() {
+        let document = webplatform::init();
+        let list = document.element_create("ul").unwrap();
+        let items: Vec<_> = (0..3).map(|_| document.element_create("li").unwrap()).collect();
+        for item in &items {
+            list.append(item);
+        }
+
+        let found = children(&list);
+        assert_eq!(found.len(), 3);
+        assert!(found.iter().zip(&items).all(|(found, item)| found == item));
+        assert!(items[1].parent().map_or(false, |parent| parent == list));
+        assert!(sibling(&items[1], 1).map_or(false, |next| next == items[2]));
+        assert!(sibling(&items[1], -1).map_or(false, |prev| prev == items[0]));
+        assert!(sibling(&items[0], -1).is_none());
+        assert!(sibling(&items[2], 1).is_none());
+        // A node without a parent has no siblings
+        assert!(sibling(&list, 1).is_none());
+    }
+
+    #[test]
+    fn reads_number_properties_as_f64() {
+        let document = webplatform::init();
+        let input = document.element_create("input").unwrap();
+        input.prop_set_str("valueAsNumber", " 1.5 ");
+        assert_eq!(f64::get_prop(&input, "valueAsNumber"), 1.5);
+        input.prop_set_str("valueAsNumber", "");
+        assert!(f64::get_prop(&input, "valueAsNumber").is_nan());
+
+        2.25f64.set_prop(&input, "valueAsNumber");
+        assert_eq!(input.prop_get_str("valueAsNumber"), "2.25");
+        assert!(!bool::get_prop(&input, "disabled"));
+        true.set_prop(&input, "disabled");
+        assert!(bool::get_prop(&input, "disabled"));
+    }
+}
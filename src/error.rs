@@ -1,30 +1,49 @@
-use ::std::io;
+use std::result;
 
+pub type Result<T> = result::Result<T, Error>;
 
 quick_error! {
     #[derive(Debug)]
     pub enum Error {
-        Io(err: io::Error) {
-            from()
-            description("io error")
-            display("I/O error: {}", err)
-            cause(err)
-        }
-        // Other(descr: &'static str) {
-        //     description(descr)
-        //     display("Error {}", descr)
-        // }
-        // IoAt { place: &'static str, err: io::Error } {
-        //     cause(err)
-        //     display(me) -> ("{} {}: {}", me.description(), place, err)
-        //     description("io error at")
-        //     from(s: String) -> {
-        //         place: "some string",
-        //         err: io::Error::new(io::ErrorKind::Other, s)
-        //     }
-        // }
-        // Discard {
-        //     from(&'static str)
-        // }
+        ElementNotFound(selector: String) {
+            description("no element matched selector")
+            display("querySelector found no results for '{}'", selector)
+        }
+        MissingData(key: String) {
+            description("app data not found")
+            display("No app data found for key '{}'", key)
+        }
+        MissingBinding(view_key: String) {
+            description("view binding not found")
+            display("No binding found for view '{}' (probably a zombie handler)", view_key)
+        }
+        TypeMismatch(what: String) {
+            description("type mismatch")
+            display("Type mismatch for {}", what)
+        }
+        BorrowConflict(what: String) {
+            description("already borrowed")
+            display("{} is already borrowed", what)
+        }
+        Render(msg: String) {
+            description("render failed")
+            display("Render failed: {}", msg)
+        }
+        Template(msg: String) {
+            description("invalid template")
+            display("Invalid template: {}", msg)
+        }
+        MissingRef(name: String) {
+            description("ref not found")
+            display("No element with ref=\"{}\" was rendered by the view", name)
+        }
+        InvalidElement(tag: String) {
+            description("cannot create element")
+            display("Cannot create element '<{}>'", tag)
+        }
+        Socket(msg: String) {
+            description("websocket error")
+            display("WebSocket error: {}", msg)
+        }
     }
-}
\ No newline at end of file
+}
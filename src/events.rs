@@ -1,3 +1,7 @@
+use webplatform::HtmlNode;
+use dom;
+use html;
+
 
 #[derive(Clone)]
 pub enum EventType {
@@ -16,6 +20,23 @@ pub enum EventType {
     Change,
 }
 
+/// Every supported event type
+pub const EVENT_TYPES: &'static [EventType] = &[
+    EventType::Click,
+    EventType::DoubleClick,
+    EventType::MouseDown,
+    EventType::MouseUp,
+    EventType::MouseEnter,
+    EventType::MouseLeave,
+    EventType::MouseOver,
+    EventType::MouseOut,
+    EventType::Input,
+    EventType::Submit,
+    EventType::Blur,
+    EventType::Focus,
+    EventType::Change,
+];
+
 impl EventType {
     pub fn name(&self) -> &'static str {
         match *self {
@@ -35,3 +56,116 @@ impl EventType {
         }
     }
 }
+
+/// The key of the item an event's element renders, read from its `q-key` (see `html::keyed`)
+///   or `data-key` attribute, or that of its closest ancestor within the view
+///
+/// Unlike `Event::index`, the key identifies the same item after rerendering or filtering.
+/// It can find the item in the component data, e.g. when items have an `id` field:
+///
+/// ```ignore
+/// view.on_each(EventType::Change, ".todo-item input", |mut evt| {
+///     let complete = evt.target.checked();
+///     let key = evt.key.clone();
+///     let mut list = evt.binding.data_mut();
+///     if let Some(item) = key.and_then(|key| key.find_mut(&mut list.items, |item| item.id)) {
+///         item.complete = complete;
+///     }
+/// });
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ItemKey(pub String);
+
+// The key of the item rendered by `matched`, from it or its closest keyed ancestor below `root`
+pub fn item_key<'doc>(matched: &HtmlNode<'doc>, root: &HtmlNode<'doc>) -> Option<ItemKey> {
+    own_key(matched).or_else(|| dom::find_within(&matched.parent()?, root, own_key))
+}
+
+fn own_key(node: &HtmlNode) -> Option<ItemKey> {
+    let key = node.attr_get_str(html::KEY_ATTR);
+    let key = if key.is_empty() { node.attr_get_str("data-key") } else { key };
+    if key.is_empty() { None } else { Some(ItemKey(key)) }
+}
+
+impl ItemKey {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The position of the item in `items` whose key, as formatted by `key_fn`, matches
+    pub fn position<T, K, F>(&self, items: &[T], key_fn: F) -> Option<usize>
+        where K: ToString,
+              F: Fn(&T) -> K
+    {
+        items.iter().position(|item| key_fn(item).to_string() == self.0)
+    }
+
+    pub fn find<'a, T, K, F>(&self, items: &'a [T], key_fn: F) -> Option<&'a T>
+        where K: ToString,
+              F: Fn(&T) -> K
+    {
+        self.position(items, key_fn).map(move |i| &items[i])
+    }
+
+    pub fn find_mut<'a, T, K, F>(&self, items: &'a mut [T], key_fn: F) -> Option<&'a mut T>
+        where K: ToString,
+              F: Fn(&T) -> K
+    {
+        match self.position(items, key_fn) {
+            Some(i) => Some(&mut items[i]),
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Todo {
+        id: u32,
+        title: &'static str,
+    }
+
+    fn todos() -> Vec<Todo> {
+        vec![Todo { id: 3, title: "first" }, Todo { id: 10, title: "second" }]
+    }
+
+    fn key(key: &str) -> ItemKey {
+        ItemKey(key.to_owned())
+    }
+
+    #[test]
+    fn finds_items_by_key() {
+        let items = todos();
+        assert_eq!(key("10").position(&items, |todo| todo.id), Some(1));
+        assert_eq!(key("3").find(&items, |todo| todo.id).map(|todo| todo.title), Some("first"));
+        assert_eq!(key("second").find(&items, |todo| todo.title).map(|todo| todo.id), Some(10));
+    }
+
+    #[test]
+    fn misses_items_without_the_key() {
+        let mut items = todos();
+        assert_eq!(key("4").position(&items, |todo| todo.id), None);
+        assert!(key("").find(&items, |todo| todo.id).is_none());
+        assert!(key("7").find_mut(&mut items, |todo| todo.id).is_none());
+        assert!(key("3").find(&[] as &[Todo], |todo| todo.id).is_none());
+    }
+
+    #[test]
+    fn compares_numeric_ids_as_formatted() {
+        let items = todos();
+        // Keys are compared as strings, so they must match how the id is rendered
+        assert_eq!(key("03").position(&items, |todo| todo.id), None);
+        assert_eq!(key("1").position(&items, |todo| todo.id), None);
+        assert_eq!(key("id-10").position(&items, |todo| format!("id-{}", todo.id)), Some(1));
+    }
+
+    #[test]
+    fn updates_items_found_by_key() {
+        let mut items = todos();
+        key("10").find_mut(&mut items, |todo| todo.id).expect("item has the key").title = "renamed";
+        assert_eq!(items[1].title, "renamed");
+        assert_eq!(items[0].title, "first");
+    }
+}
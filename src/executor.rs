@@ -0,0 +1,369 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use futures::{Async, Future};
+use futures::executor::{self, Notify, Spawn};
+use state::{AppState, TypedKey};
+use task::TaskHandle;
+
+use AppContext;
+
+type SpawnedFuture<'doc> = Spawn<Box<Future<Item = (), Error = ()> + 'doc>>;
+
+// A spawned future along with the handle that cancels it
+struct Spawned<'doc> {
+    future: SpawnedFuture<'doc>,
+    task: TaskHandle<'doc>,
+}
+
+/// Drives spawned futures on the quasar event loop
+///
+/// Futures are polled when spawned, and whenever they have been woken up by the time
+///   quasar processes the render queue (i.e. after each event, timer, response or message),
+///   or when the app calls `QuasarApp::run_tasks`. Waking a future also schedules a flush,
+///   so futures woken outside of quasar's own callbacks are polled too.
+pub struct Executor<'doc> {
+    futures: RefCell<HashMap<usize, Spawned<'doc>>>,
+    woken: Arc<WakeQueue>,
+    next_id: Cell<usize>,
+    running: Cell<bool>,
+}
+
+// Ids of futures that need polled, which may be woken from any thread
+struct WakeQueue {
+    ids: Mutex<Vec<usize>>,
+    // Whether a flush was scheduled since the queue was last drained
+    scheduled: AtomicBool,
+    schedule: fn(),
+}
+
+impl Notify for WakeQueue {
+    fn notify(&self, id: usize) {
+        self.ids.lock().unwrap().push(id);
+        if !self.scheduled.swap(true, Ordering::SeqCst) {
+            (self.schedule)();
+        }
+    }
+}
+
+thread_local! {
+    // The app flushed by `schedule_wake`, since wakers can only reach `'static` state
+    static WAKE_APP: RefCell<Option<Weak<AppState<'static>>>> = RefCell::new(None);
+}
+
+/// Process the render queue of the app set with `set_wake_app` from a timeout on its `Timers`,
+///   polling any woken futures
+///
+/// Going through the app's timers means `ManualTimers` can fire wakes without a browser.
+pub fn schedule_wake() {
+    let app = WAKE_APP.with(|app| app.borrow().clone());
+    let timers = match app.as_ref().and_then(Weak::upgrade) {
+        Some(app) => app.timers(),
+        None => return,
+    };
+    timers.set_timeout(0, Box::new(move || {
+        if let Some(app) = app.as_ref().and_then(Weak::upgrade) {
+            app.flush_render_queue();
+        }
+    }));
+}
+
+/// Set the app whose futures are polled after `schedule_wake`
+pub fn set_wake_app(app: &Rc<AppState<'static>>) {
+    WAKE_APP.with(|wake_app| *wake_app.borrow_mut() = Some(Rc::downgrade(app)));
+}
+
+impl<'doc> Executor<'doc> {
+    /// An executor calling `schedule` when futures are woken, which should arrange for `run`
+    ///   to be called soon (e.g. `schedule_wake`)
+    pub fn new(schedule: fn()) -> Executor<'doc> {
+        Executor {
+            futures: RefCell::new(HashMap::new()),
+            woken: Arc::new(WakeQueue {
+                ids: Mutex::new(vec![]),
+                scheduled: AtomicBool::new(false),
+                schedule: schedule,
+            }),
+            next_id: Cell::new(0),
+            running: Cell::new(false),
+        }
+    }
+
+    /// An executor that is only driven by calls to `run`, for use without an event loop
+    pub fn headless() -> Executor<'doc> {
+        fn ignore() {}
+        Executor::new(ignore)
+    }
+
+    fn add(&self, future: Box<Future<Item = (), Error = ()> + 'doc>, task: TaskHandle<'doc>) -> usize {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        self.futures.borrow_mut().insert(id, Spawned { future: executor::spawn(future), task: task });
+        self.woken.notify(id);
+        id
+    }
+
+    fn remove(&self, id: usize) {
+        self.futures.borrow_mut().remove(&id);
+    }
+
+    /// Poll woken futures until none are left to poll
+    ///
+    /// This is a no-op when called from within a future, since the outer call
+    ///   will poll anything woken in the meantime.
+    pub fn run(&self) {
+        if self.running.replace(true) {
+            return;
+        }
+        let _running = RunningGuard(&self.running);
+        loop {
+            self.woken.scheduled.store(false, Ordering::SeqCst);
+            let woken = ::std::mem::replace(&mut *self.woken.ids.lock().unwrap(), vec![]);
+            if woken.is_empty() {
+                break;
+            }
+            for id in woken {
+                // Take the future out while polling it, since it may spawn or cancel futures
+                let spawned = self.futures.borrow_mut().remove(&id);
+                if let Some(mut spawned) = spawned {
+                    match spawned.future.poll_future_notify(&self.woken, id) {
+                        // Cancelling the task while it was taken out could not remove it
+                        Ok(Async::NotReady) if !spawned.task.is_done() => {
+                            self.futures.borrow_mut().insert(id, spawned);
+                        }
+                        Ok(Async::NotReady) | Ok(Async::Ready(())) | Err(()) => (),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether any spawned futures have not completed
+    pub fn is_idle(&self) -> bool {
+        self.futures.borrow().is_empty()
+    }
+}
+
+// Clears the running flag once `Executor::run` returns, even if polling panicked
+struct RunningGuard<'a>(&'a Cell<bool>);
+
+impl<'a> Drop for RunningGuard<'a> {
+    fn drop(&mut self) {
+        self.0.set(false);
+    }
+}
+
+/// Drive `future` to completion, then call `done` with its result and process the render queue
+///
+/// When spawned for a view, the future is dropped if the view is unbound first.
+pub fn spawn<'doc, F, C>(app: &Rc<AppState<'doc>>, view_id: Option<TypedKey>, future: F, done: C) -> TaskHandle<'doc>
+    where F: Future + 'doc,
+          C: FnOnce(AppContext<'doc>, Result<F::Item, F::Error>) + 'doc
+{
+    let task = TaskHandle::new();
+    let completion = {
+        let app = app.clone();
+        let task = task.clone();
+        let view_id = view_id.clone();
+        move |result| {
+            if !task.is_done() {
+                task.finish();
+                done(AppContext::new(app.clone(), view_id), result);
+                app.flush_render_queue();
+            }
+            Ok(())
+        }
+    };
+
+    let executor = app.executor();
+    let id = executor.add(Box::new(future.then(completion)), task.clone());
+    {
+        let executor = Rc::downgrade(&executor);
+        task.on_cancel(move || {
+            if let Some(executor) = executor.upgrade() {
+                executor.remove(id);
+            }
+        });
+    }
+    if let Some(ref view_id) = view_id {
+        app.add_task(view_id, task.clone());
+    }
+    executor.run();
+    task
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT};
+    use webplatform;
+    use timer::ManualTimers;
+    use trace::TraceRecord;
+    use std::panic::{self, AssertUnwindSafe};
+    use futures::future;
+    use futures::sync::oneshot;
+
+    static SCHEDULED: AtomicUsize = ATOMIC_USIZE_INIT;
+
+    fn count_schedule() {
+        SCHEDULED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn headless_run_polls_until_complete() {
+        let executor = Executor::headless();
+        let (tx, rx) = oneshot::channel::<u32>();
+        let result = Rc::new(Cell::new(None));
+
+        let id = {
+            let result = result.clone();
+            executor.add(Box::new(rx.map(move |value| result.set(Some(value))).map_err(|_| ())), TaskHandle::new())
+        };
+        executor.run();
+        assert!(!executor.is_idle());
+        assert_eq!(result.get(), None);
+
+        tx.send(7).unwrap();
+        executor.run();
+        assert!(executor.is_idle());
+        assert_eq!(result.get(), Some(7));
+
+        // Removing a completed future is a no-op
+        executor.remove(id);
+        assert!(executor.is_idle());
+    }
+
+    #[test]
+    fn removed_futures_are_not_polled() {
+        let executor = Executor::headless();
+        let (tx, rx) = oneshot::channel::<u32>();
+        let polled = Rc::new(Cell::new(false));
+
+        let id = {
+            let polled = polled.clone();
+            executor.add(Box::new(rx.map(move |_| polled.set(true)).map_err(|_| ())), TaskHandle::new())
+        };
+        executor.remove(id);
+        // Removing the future dropped its receiver
+        assert!(tx.send(1).is_err());
+        executor.run();
+        assert!(executor.is_idle());
+        assert!(!polled.get());
+    }
+
+    #[test]
+    fn futures_cancelled_while_polled_are_dropped() {
+        let executor = Executor::headless();
+        let task = TaskHandle::new();
+        let future = {
+            let task = task.clone();
+            future::lazy(move || {
+                task.cancel();
+                future::empty::<(), ()>()
+            })
+        };
+        executor.add(Box::new(future), task.clone());
+        executor.run();
+        assert!(task.is_cancelled());
+        assert!(executor.is_idle());
+    }
+
+    #[test]
+    fn run_recovers_from_a_panicking_future() {
+        let executor = Executor::headless();
+        executor.add(Box::new(future::lazy(|| -> Result<(), ()> { panic!("poll failed") })), TaskHandle::new());
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| executor.run())).is_err());
+
+        let polled = Rc::new(Cell::new(false));
+        {
+            let polled = polled.clone();
+            executor.add(Box::new(future::lazy(move || -> Result<(), ()> {
+                polled.set(true);
+                Ok(())
+            })), TaskHandle::new());
+        }
+        executor.run();
+        assert!(polled.get());
+    }
+
+    #[test]
+    fn waking_schedules_one_flush_until_run() {
+        let executor = Executor::new(count_schedule);
+        let (tx, rx) = oneshot::channel::<u32>();
+        executor.add(Box::new(rx.map(|_| ()).map_err(|_| ())), TaskHandle::new());
+        let before = SCHEDULED.load(Ordering::SeqCst);
+
+        // Adding the future woke it, which scheduled a flush that has not happened yet
+        assert_eq!(before, 1);
+        executor.run();
+        assert!(!executor.is_idle());
+
+        tx.send(1).unwrap();
+        assert_eq!(SCHEDULED.load(Ordering::SeqCst), 2);
+        executor.run();
+        assert!(executor.is_idle());
+    }
+    // An app whose wakes fire when `timers` advances, counting how often its render queue was processed
+    fn wake_app() -> (Rc<AppState<'static>>, Rc<ManualTimers<'static>>, Rc<Cell<usize>>) {
+        let app = Rc::new(AppState::new(webplatform::init()));
+        let timers = Rc::new(ManualTimers::new());
+        let flushes = Rc::new(Cell::new(0));
+        app.set_timers(timers.clone());
+        {
+            let flushes = flushes.clone();
+            app.set_tracer(Some(Rc::new(move |record: &TraceRecord| {
+                if let TraceRecord::RenderQueueProcessed { .. } = *record {
+                    flushes.set(flushes.get() + 1);
+                }
+            })));
+        }
+        set_wake_app(&app);
+        (app, timers, flushes)
+    }
+
+    #[test]
+    fn spawned_futures_complete_when_woken() {
+        let (app, timers, flushes) = wake_app();
+        let (tx, rx) = oneshot::channel::<u32>();
+        let result = Rc::new(Cell::new(None));
+        let task = {
+            let result = result.clone();
+            spawn(&app, None, rx, move |_, value| result.set(value.ok()))
+        };
+
+        timers.advance(0);
+        assert_eq!(result.get(), None);
+        assert!(!task.is_done());
+
+        let before = flushes.get();
+        tx.send(7).unwrap();
+        assert_eq!(timers.pending(), 1);
+        timers.advance(0);
+        assert_eq!(result.get(), Some(7));
+        assert!(task.is_done() && !task.is_cancelled());
+        assert!(flushes.get() > before);
+        assert!(app.executor().is_idle());
+    }
+
+    #[test]
+    fn unbinding_a_view_drops_its_futures() {
+        let (app, timers, _) = wake_app();
+        let view_id = TypedKey::new::<u32>("view");
+        let (tx, rx) = oneshot::channel::<u32>();
+        let called = Rc::new(Cell::new(false));
+        let task = {
+            let called = called.clone();
+            spawn(&app, Some(view_id.clone()), rx, move |_, _| called.set(true))
+        };
+
+        app.discard_binding(&view_id);
+        assert!(task.is_cancelled());
+        assert!(app.executor().is_idle());
+        // Dropping the future dropped its receiver
+        assert!(tx.send(1).is_err());
+        timers.advance(1000);
+        assert!(!called.get());
+    }
+}
@@ -0,0 +1,341 @@
+//! Loading JSON over HTTP into app data
+//!
+//! `AppContext::fetch` stores a `FetchState` in an app data partition, which is `Loading`
+//!   until the response is decoded, and rerenders the partition's observers on completion:
+//!
+//! ```ignore
+//! evt.app.fetch::<Vec<Item>>("items", Request::get("/api/items"));
+//!
+//! // when rendering
+//! match *app.data::<FetchState<Vec<Item>>>("items").unwrap() {
+//!     FetchState::Loading => ...,
+//!     FetchState::Loaded(ref items) => ...,
+//!     FetchState::Failed(ref err) => ...,
+//! }
+//! ```
+//!
+//! Requests are sent through a `Transport`, which defaults to the browser's XHR
+//!   and can be replaced with `QuasarApp::set_transport`, e.g. by a `StubTransport` in tests.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use rustc_serialize::Decodable;
+use rustc_serialize::json;
+use platform;
+use state::{AppState, TypedKey};
+use task::TaskHandle;
+
+/// An HTTP request
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+}
+
+impl Request {
+    pub fn new(method: &str, url: &str) -> Request {
+        Request {
+            method: method.to_owned(),
+            url: url.to_owned(),
+            headers: vec![],
+            body: None,
+        }
+    }
+
+    pub fn get(url: &str) -> Request {
+        Request::new("GET", url)
+    }
+
+    /// A POST request with a JSON body
+    pub fn post(url: &str, body: &str) -> Request {
+        Request::new("POST", url).header("Content-Type", "application/json").body(body)
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Request {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    pub fn body(mut self, body: &str) -> Request {
+        self.body = Some(body.to_owned());
+        self
+    }
+}
+
+/// An HTTP response
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+/// Called once with the response, or with an error message if no response was received
+pub type ResponseCallback<'doc> = Box<FnMut(Result<Response, String>) + 'doc>;
+
+/// Sends HTTP requests for `AppContext::fetch`
+pub trait Transport<'doc> {
+    fn send(&self, request: Request, done: ResponseCallback<'doc>);
+}
+
+/// The browser's XMLHttpRequest
+///
+/// Responses arrive from the browser's event loop, so only `'static` callbacks can be sent.
+pub struct XhrTransport;
+
+impl Transport<'static> for XhrTransport {
+    fn send(&self, request: Request, mut done: ResponseCallback<'static>) {
+        let url = request.url.clone();
+        platform::xhr(&request.method,
+                      &request.url,
+                      &request.headers,
+                      request.body.as_ref().map(|body| &**body),
+                      move |status, body| {
+            if status == 0 {
+                done(Err(format!("Request to '{}' failed", url)));
+            } else {
+                done(Ok(Response { status: status, body: body }));
+            }
+        });
+    }
+}
+
+/// A transport answering requests with canned responses, for testing without a server
+///
+/// Requests are held until `respond_all` is called, so that tests can observe the loading state.
+pub struct StubTransport<'doc> {
+    responses: RefCell<HashMap<(String, String), Response>>,
+    pending: RefCell<Vec<(Request, ResponseCallback<'doc>)>>,
+}
+
+impl<'doc> Default for StubTransport<'doc> {
+    fn default() -> StubTransport<'doc> {
+        StubTransport::new()
+    }
+}
+
+impl<'doc> StubTransport<'doc> {
+    pub fn new() -> StubTransport<'doc> {
+        StubTransport {
+            responses: RefCell::new(HashMap::new()),
+            pending: RefCell::new(vec![]),
+        }
+    }
+
+    /// Respond to requests for `method` and `url` with `status` and `body`
+    pub fn stub(&self, method: &str, url: &str, status: u16, body: &str) {
+        let response = Response { status: status, body: body.to_owned() };
+        self.responses.borrow_mut().insert((method.to_owned(), url.to_owned()), response);
+    }
+
+    /// Requests that have been sent but not yet responded to
+    pub fn pending(&self) -> Vec<Request> {
+        self.pending.borrow().iter().map(|&(ref request, _)| request.clone()).collect()
+    }
+
+    /// Respond to every pending request, failing those without a stubbed response
+    pub fn respond_all(&self) {
+        let pending = ::std::mem::replace(&mut *self.pending.borrow_mut(), vec![]);
+        for (request, mut done) in pending {
+            let key = (request.method.clone(), request.url.clone());
+            let response = self.responses.borrow().get(&key).cloned();
+            match response {
+                Some(response) => done(Ok(response)),
+                None => done(Err(format!("No stubbed response for {} '{}'", request.method, request.url))),
+            }
+        }
+    }
+}
+
+impl<'doc> Transport<'doc> for StubTransport<'doc> {
+    fn send(&self, request: Request, done: ResponseCallback<'doc>) {
+        self.pending.borrow_mut().push((request, done));
+    }
+}
+
+/// The status of data loaded by `AppContext::fetch`
+#[derive(Debug, Clone, PartialEq)]
+pub enum FetchState<T> {
+    Loading,
+    Loaded(T),
+    Failed(String),
+}
+
+impl<T> FetchState<T> {
+    pub fn is_loading(&self) -> bool {
+        match *self {
+            FetchState::Loading => true,
+            _ => false,
+        }
+    }
+
+    pub fn value(&self) -> Option<&T> {
+        match *self {
+            FetchState::Loaded(ref value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        match *self {
+            FetchState::Failed(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Send `request`, storing its decoded response as a `FetchState<T>` in app data at `key`
+///
+/// A fetch still in flight for the same key is cancelled, so that its response cannot
+///   overwrite this one. When started for a view, the response is ignored if the view
+///   is unbound first. A response arriving while the data at `key` is borrowed is logged and dropped.
+pub fn fetch<'doc, T>(app: &Rc<AppState<'doc>>, view_id: Option<TypedKey>, key: &str, request: Request) -> TaskHandle<'doc>
+    where T: 'static + Decodable
+{
+    let task = TaskHandle::new();
+    app.replace_fetch(TypedKey::new::<FetchState<T>>(key), task.clone());
+    if let Some(ref view_id) = view_id {
+        app.add_task(view_id, task.clone());
+    }
+    app.data_set(key, FetchState::<T>::Loading);
+
+    let done = {
+        let app = app.clone();
+        let task = task.clone();
+        let key = key.to_owned();
+        let url = request.url.clone();
+        move |response: Result<Response, String>| {
+            if task.is_done() {
+                return;
+            }
+            task.finish();
+
+            let state = match response {
+                Ok(ref response) if response.status < 200 || response.status >= 300 => {
+                    FetchState::Failed(format!("Request to '{}' failed with status {}", url, response.status))
+                }
+                Ok(response) => {
+                    match json::decode::<T>(&response.body) {
+                        Ok(value) => FetchState::Loaded(value),
+                        Err(err) => FetchState::Failed(format!("Failed to decode response from '{}': {}", url, err)),
+                    }
+                }
+                Err(err) => FetchState::Failed(err),
+            };
+            if let Err(err) = app.try_data_set(&key, state) {
+                warn!(target: "quasar::fetch", "Dropped response from '{}': {}", url, err);
+            }
+            app.flush_render_queue();
+        }
+    };
+    app.transport().send(request, Box::new(done));
+    task
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webplatform;
+
+    fn app() -> (Rc<AppState<'static>>, Rc<StubTransport<'static>>) {
+        let app = Rc::new(AppState::new(webplatform::init()));
+        let transport = Rc::new(StubTransport::new());
+        app.set_transport(transport.clone());
+        (app, transport)
+    }
+
+    fn state(app: &AppState) -> FetchState<Vec<u32>> {
+        app.data::<FetchState<Vec<u32>>>("items").expect("fetch state was not set").clone()
+    }
+
+    #[test]
+    fn loads_decoded_response() {
+        let (app, transport) = app();
+        transport.stub("GET", "/items", 200, "[1, 2]");
+
+        let task = fetch::<Vec<u32>>(&app, None, "items", Request::get("/items"));
+        assert_eq!(state(&app), FetchState::Loading);
+        assert_eq!(transport.pending(), vec![Request::get("/items")]);
+        assert!(!task.is_done());
+
+        transport.respond_all();
+        assert_eq!(state(&app), FetchState::Loaded(vec![1, 2]));
+        assert!(task.is_done() && !task.is_cancelled());
+    }
+
+    #[test]
+    fn fails_on_error_status() {
+        let (app, transport) = app();
+        transport.stub("GET", "/items", 404, "[1, 2]");
+
+        fetch::<Vec<u32>>(&app, None, "items", Request::get("/items"));
+        transport.respond_all();
+        let err = state(&app).error().map(str::to_owned).expect("fetch should have failed");
+        assert!(err.contains("status 404"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn fails_on_undecodable_response() {
+        let (app, transport) = app();
+        transport.stub("GET", "/items", 200, "{\"not\": \"a list\"}");
+
+        fetch::<Vec<u32>>(&app, None, "items", Request::get("/items"));
+        transport.respond_all();
+        let err = state(&app).error().map(str::to_owned).expect("fetch should have failed");
+        assert!(err.contains("Failed to decode"), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn fails_without_response() {
+        let (app, transport) = app();
+
+        fetch::<Vec<u32>>(&app, None, "items", Request::get("/items"));
+        transport.respond_all();
+        assert!(state(&app).error().is_some());
+    }
+
+    #[test]
+    fn refetching_cancels_previous_request() {
+        let (app, transport) = app();
+        transport.stub("GET", "/old", 200, "[1]");
+        transport.stub("GET", "/new", 200, "[2]");
+
+        let old = fetch::<Vec<u32>>(&app, None, "items", Request::get("/old"));
+        let new = fetch::<Vec<u32>>(&app, None, "items", Request::get("/new"));
+        assert!(old.is_cancelled());
+
+        // The stale response arrives first, and must not be stored
+        transport.respond_all();
+        assert_eq!(state(&app), FetchState::Loaded(vec![2]));
+        assert!(new.is_done() && !new.is_cancelled());
+    }
+
+    #[test]
+    fn drops_response_while_data_is_borrowed() {
+        let (app, transport) = app();
+        transport.stub("GET", "/items", 200, "[1]");
+
+        let task = fetch::<Vec<u32>>(&app, None, "items", Request::get("/items"));
+        {
+            let _loading = app.try_data::<FetchState<Vec<u32>>>("items").unwrap();
+            transport.respond_all();
+        }
+        assert_eq!(state(&app), FetchState::Loading);
+        assert!(task.is_done());
+    }
+
+    #[test]
+    fn fetches_for_other_keys_are_independent() {
+        let (app, transport) = app();
+        transport.stub("GET", "/items", 200, "[1]");
+
+        let items = fetch::<Vec<u32>>(&app, None, "items", Request::get("/items"));
+        fetch::<Vec<u32>>(&app, None, "other", Request::get("/items"));
+        assert!(!items.is_cancelled());
+
+        transport.respond_all();
+        assert_eq!(state(&app), FetchState::Loaded(vec![1]));
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::BTreeMap;
+use std::collections::btree_map;
+use rustc_serialize::{Decodable, Encodable, Encoder};
+use rustc_serialize::json::{self, Json, DecoderError};
+
+use {Event, HasBind, Node, Queryable, Renderable, View};
+
+/// Validation errors of a form, keyed by control name
+///
+/// Errors that are not specific to a single control (e.g. a value that could not be decoded)
+///   are recorded under the empty name `""`.
+/// Encodes as a map of control names to lists of messages, for use in templates.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FormErrors {
+    fields: BTreeMap<String, Vec<String>>,
+}
+
+impl FormErrors {
+    pub fn new() -> FormErrors {
+        FormErrors::default()
+    }
+
+    /// Record an error message for the control named `field`
+    pub fn add(&mut self, field: &str, message: &str) {
+        self.fields.entry(field.to_owned()).or_insert_with(Vec::new).push(message.to_owned());
+    }
+
+    /// Error messages for the control named `field`
+    pub fn get(&self, field: &str) -> &[String] {
+        self.fields.get(field).map(|messages| &messages[..]).unwrap_or(&[])
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn iter(&self) -> btree_map::Iter<String, Vec<String>> {
+        self.fields.iter()
+    }
+}
+
+impl Encodable for FormErrors {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        self.fields.encode(s)
+    }
+}
+
+// Where a form publishes its errors after each submission
+enum ErrorTarget<R> {
+    Discard,
+    Component(Box<Fn(&mut R) -> &mut FormErrors>),
+    AppData(String),
+}
+
+/// Decodes and validates a submitted form into `T` for a view of component `R`
+///
+/// Use with `View::on_submit`:
+///
+/// ```ignore
+/// let form = Form::new()
+///     .validate(|signup: &Signup, errors| if signup.name.is_empty() {
+///         errors.add("name", "Name is required");
+///     })
+///     .errors_in_data(|d| &mut d.errors);
+///
+/// view.on_submit("form", form, |mut evt, signup| {
+///     evt.binding.data_mut().users.push(signup);
+/// });
+/// ```
+pub struct Form<R, T> {
+    validators: Vec<Box<Fn(&T, &mut FormErrors)>>,
+    errors: ErrorTarget<R>,
+}
+
+impl<R: 'static + Renderable, T: Decodable> Form<R, T> {
+    pub fn new() -> Form<R, T> {
+        Form {
+            validators: vec![],
+            errors: ErrorTarget::Discard,
+        }
+    }
+
+    /// Add a validator, which records any problems with the decoded value in `FormErrors`
+    pub fn validate<V>(mut self, validator: V) -> Form<R, T>
+        where V: Fn(&T, &mut FormErrors) + 'static
+    {
+        self.validators.push(Box::new(validator));
+        self
+    }
+
+    /// Publish errors after each submission into a field of the component data
+    pub fn errors_in_data<F>(mut self, field: F) -> Form<R, T>
+        where F: Fn(&mut R) -> &mut FormErrors + 'static
+    {
+        self.errors = ErrorTarget::Component(Box::new(field));
+        self
+    }
+
+    /// Publish errors after each submission as app data at `key`
+    pub fn errors_in_app_data(mut self, key: &str) -> Form<R, T> {
+        self.errors = ErrorTarget::AppData(key.to_owned());
+        self
+    }
+
+    /// Decode and validate the form that triggered `evt`, publishing the resulting errors
+    ///
+    /// Returns the decoded value only if it had no errors.
+    pub fn submit<'e, 'doc>(&self, evt: &mut Event<'e, View<'doc, R>>) -> Option<T> {
+        let (value, errors) = match decode_form::<T>(&evt.target) {
+            Ok(value) => {
+                let mut errors = FormErrors::new();
+                for validator in &self.validators {
+                    validator(&value, &mut errors);
+                }
+                (Some(value), errors)
+            }
+            Err(errors) => (None, errors),
+        };
+
+        let valid = errors.is_empty();
+        match self.errors {
+            ErrorTarget::Discard => (),
+            ErrorTarget::Component(ref field) => *field(&mut evt.binding.data_mut()) = errors,
+            ErrorTarget::AppData(ref key) => evt.app.data_set(key, errors),
+        }
+
+        if valid { value } else { None }
+    }
+}
+
+/// Decode the named controls within `form` into `T`
+///
+/// Checkboxes decode as booleans, only the checked radio button of a group is included,
+///   and all other controls decode from their string value (which numeric fields will parse).
+pub fn decode_form<T: Decodable>(form: &Node) -> Result<T, FormErrors> {
+    let mut fields = json::Object::new();
+    for control in form.query_all("[name]") {
+        let value = match &*control.get_attr("type") {
+            "checkbox" => Json::Boolean(control.checked()),
+            "radio" if !control.checked() => continue,
+            _ => Json::String(control.value()),
+        };
+        fields.insert(control.get_attr("name"), value);
+    }
+
+    let mut decoder = json::Decoder::new(Json::Object(fields));
+    Decodable::decode(&mut decoder).map_err(|err| {
+        let mut errors = FormErrors::new();
+        match err {
+            DecoderError::MissingFieldError(field) => errors.add(&field, "is required"),
+            err => errors.add("", &err.to_string()),
+        }
+        errors
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use webplatform;
+    use state::AppState;
+    use view::bind_component;
+    use {AppContext, Component, Queryable, Result};
+
+    struct Signup {
+        errors: FormErrors,
+    }
+
+    impl Renderable for Signup {
+        fn render(&self, _node: &Node, _app: &AppContext) -> Result<String> {
+            Ok("<form><input name=\"name\" value=\"\"></form>".to_owned())
+        }
+    }
+
+    impl Component for Signup {
+        fn onload(_view: &View<Self>) {}
+    }
+
+    fn form<'doc>(app: &Rc<AppState<'doc>>, markup: &str) -> Node<'doc> {
+        let node = app.document.element_create("form").expect("form is a valid tag");
+        node.html_set(markup);
+        Node::new(app.clone(), Rc::new(node))
+    }
+
+    // Submit the form rendered by a bound `Signup`, with `name` entered
+    fn submit<'doc>(app: &Rc<AppState<'doc>>, form: &Form<Signup, HashMap<String, String>>, name: &str)
+                    -> (View<'doc, Signup>, Option<HashMap<String, String>>) {
+        let body = app.document.element_query("body").expect("document has a body");
+        body.html_set("<div id=\"signup\"></div>");
+        let node = app.document.element_query("#signup").expect("container was rendered");
+        let view = bind_component(app, node, Signup { errors: FormErrors::new() }).expect("signup renders");
+
+        let target = Node::new(app.clone(), Rc::new(app.document.element_query("form").expect("form was rendered")));
+        target.query("[name=name]").expect("name field was rendered").set("value", name);
+        let mut evt = Event {
+            matched: target.clone(),
+            key: None,
+            target: target,
+            binding: view,
+            app: AppContext::new(app.clone(), None),
+            index: 0,
+        };
+        let value = form.submit(&mut evt);
+        (evt.binding, value)
+    }
+
+    fn require_name<R: 'static + Renderable>() -> Form<R, HashMap<String, String>> {
+        Form::new().validate(|values: &HashMap<String, String>, errors| if values["name"].is_empty() {
+            errors.add("name", "Name is required");
+        })
+    }
+
+    #[test]
+    fn decodes_checkboxes_as_booleans() {
+        let app = Rc::new(AppState::new(webplatform::init()));
+        let form = form(&app, "<input type=\"checkbox\" name=\"news\" checked>\
+                               <input type=\"checkbox\" name=\"terms\" value=\"yes\">");
+        let values: HashMap<String, bool> = decode_form(&form).unwrap();
+        assert_eq!(values.get("news"), Some(&true));
+        assert_eq!(values.get("terms"), Some(&false));
+    }
+
+    #[test]
+    fn skips_unchecked_radios() {
+        let app = Rc::new(AppState::new(webplatform::init()));
+        let form = form(&app, "<input type=\"radio\" name=\"size\" value=\"s\">\
+                               <input type=\"radio\" name=\"size\" value=\"m\" checked>\
+                               <input type=\"radio\" name=\"size\" value=\"l\">\
+                               <input type=\"radio\" name=\"color\" value=\"red\">\
+                               <input name=\"label\" value=\"tee\">");
+        let values: HashMap<String, String> = decode_form(&form).unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values["size"], "m");
+        assert_eq!(values["label"], "tee");
+    }
+
+    #[test]
+    fn records_undecodable_values_without_a_field() {
+        let app = Rc::new(AppState::new(webplatform::init()));
+        let form = form(&app, "<input name=\"age\" value=\"old\">");
+        let errors = decode_form::<HashMap<String, u32>>(&form).unwrap_err();
+        assert_eq!(errors.iter().count(), 1);
+        assert_eq!(errors.get("").len(), 1);
+    }
+
+    #[test]
+    fn publishes_errors_into_component_data() {
+        let app = Rc::new(AppState::new(webplatform::init()));
+        let form = require_name().errors_in_data(|d: &mut Signup| &mut d.errors);
+
+        let (view, value) = submit(&app, &form, "");
+        assert_eq!(value, None);
+        assert_eq!(view.data().errors.get("name"), &["Name is required".to_owned()]);
+
+        let (view, value) = submit(&app, &form, "Ann");
+        assert_eq!(value.map(|values| values["name"].clone()), Some("Ann".to_owned()));
+        assert!(view.data().errors.is_empty());
+    }
+
+    #[test]
+    fn publishes_errors_as_app_data() {
+        let app = Rc::new(AppState::new(webplatform::init()));
+        let form = require_name().errors_in_app_data("signup-errors");
+
+        assert_eq!(submit(&app, &form, "").1, None);
+        assert_eq!(app.data::<FormErrors>("signup-errors").unwrap().get("name").len(), 1);
+
+        assert!(submit(&app, &form, "Ann").1.is_some());
+        assert!(app.data::<FormErrors>("signup-errors").unwrap().is_empty());
+    }
+}
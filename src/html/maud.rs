@@ -0,0 +1,62 @@
+//! Rendering components with [maud](https://crates.io/crates/maud)
+
+use std::fmt::Display;
+use maud::{Markup, PreEscaped};
+
+use super::SafeHtml;
+use {AppContext, Node, Renderable, Result};
+
+/// Render a component with maud's `html!`, which makes it `Renderable`
+///
+/// ```ignore
+/// impl RenderMarkup for CounterData {
+///     fn markup(&self, _node: &Node, _app: &AppContext) -> Markup {
+///         html! { p { "Count: " (self.count) } button { "+1" } }
+///     }
+/// }
+/// ```
+pub trait RenderMarkup {
+    fn markup(&self, node: &Node, app: &AppContext) -> Markup;
+}
+
+impl<T: 'static + RenderMarkup> Renderable for T {
+    fn render(&self, node: &Node, app: &AppContext) -> Result<String> {
+        Ok(self.markup(node, app).into_string())
+    }
+}
+
+impl Renderable for Markup {
+    fn render(&self, _node: &Node, _app: &AppContext) -> Result<String> {
+        Ok(self.0.clone())
+    }
+}
+
+impl From<Markup> for SafeHtml {
+    fn from(markup: Markup) -> SafeHtml {
+        SafeHtml::trusted(markup.into_string())
+    }
+}
+
+/// Placeholder element for the child component named `name` (see `html::child`)
+pub fn child(name: &str) -> Markup {
+    PreEscaped(super::child(name).into_string())
+}
+
+/// Render each item wrapped in a `tag` element carrying its stable key (see `html::keyed`)
+///
+/// ```ignore
+/// ul { (keyed("li", &self.items, |item| item.id, |item| html! { (item.label) })) }
+/// ```
+pub fn keyed<I, K, D, F>(tag: &str, items: I, key: K, item: F) -> Markup
+    where I: IntoIterator,
+          K: Fn(&I::Item) -> D,
+          D: Display,
+          F: Fn(&I::Item) -> Markup
+{
+    let mut out = String::new();
+    for i in items {
+        let content = SafeHtml::from(item(&i));
+        out.push_str(super::keyed(tag, &key(&i).to_string(), &content).as_str());
+    }
+    PreEscaped(out)
+}
@@ -0,0 +1,102 @@
+//! HTML helpers shared by quasar's rendering and template engine integrations
+//!
+//! Every helper escapes the values it interpolates with `escape`, the same escaping applied
+//!   by quasar's built-in template engine, so they are safe to emit unescaped from any engine.
+
+mod safe;
+mod sanitize;
+#[cfg(feature = "maud")]
+pub mod maud;
+
+pub use self::safe::{SafeHtml, Text};
+pub use self::sanitize::sanitize;
+
+/// Attribute marking the placeholder element a child component is bound into
+pub const CHILD_ATTR: &'static str = "q-child";
+
+/// Attribute naming an element for lookup with `View::ref`
+pub const REF_ATTR: &'static str = "ref";
+
+/// Attribute holding the stable key of an item emitted by `keyed`
+pub const KEY_ATTR: &'static str = "q-key";
+
+/// Escape text for use in HTML content or attribute values
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Placeholder element for the child component named `name`
+///
+/// Bind the child into it with the selector from `child_selector` using `View::bind`,
+///   which rerenders the child whenever the parent's output replaces it.
+pub fn child(name: &str) -> SafeHtml {
+    SafeHtml::trusted(format!("<div {}=\"{}\"></div>", CHILD_ATTR, escape(name)))
+}
+
+/// Selector matching the placeholder emitted by `child`
+pub fn child_selector(name: &str) -> String {
+    format!("[{}=\"{}\"]", CHILD_ATTR, escape(name))
+}
+
+/// Wrap the rendered `content` of a list item in a `tag` element carrying its stable `key`
+pub fn keyed(tag: &str, key: &str, content: &SafeHtml) -> SafeHtml {
+    SafeHtml::trusted(format!("<{tag} {attr}=\"{key}\">{content}</{tag}>",
+                              tag = tag,
+                              attr = KEY_ATTR,
+                              key = escape(key),
+                              content = content))
+}
+
+/// Opt a type rendered by its `Display` impl (e.g. `#[derive(BartDisplay)]`) into `Renderable`
+///
+/// Bart escapes interpolated values itself, so its output is used as-is:
+///
+/// ```ignore
+/// #[derive(BartDisplay)]
+/// #[template = "src/counter.html"]
+/// struct CounterData { count: u32 }
+///
+/// bart_renderable!(CounterData);
+/// ```
+#[cfg(feature = "bart")]
+#[macro_export]
+macro_rules! bart_renderable {
+    ($($ty:ty),+) => {
+        $(
+            impl $crate::Renderable for $ty {
+                fn render(&self, _node: &$crate::Node, _app: &$crate::AppContext) -> $crate::Result<String> {
+                    Ok(self.to_string())
+                }
+            }
+        )+
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_markup_characters() {
+        assert_eq!(escape("<a href=\"x\" title='y'>&</a>"),
+                   "&lt;a href=&quot;x&quot; title=&#39;y&#39;&gt;&amp;&lt;/a&gt;");
+        assert_eq!(escape("plain text"), "plain text");
+    }
+
+    #[test]
+    fn escapes_helper_arguments() {
+        assert_eq!(child("a\"b").as_str(), "<div q-child=\"a&quot;b\"></div>");
+        assert_eq!(child_selector("a\"b"), "[q-child=\"a&quot;b\"]");
+    }
+}
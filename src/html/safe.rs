@@ -0,0 +1,132 @@
+use std::fmt::{self, Display};
+
+use super::{escape, sanitize};
+use {AppContext, Component, Node, Renderable, Result, View};
+
+/// Markup that is safe to insert into the DOM as-is
+///
+/// Plain text only becomes `SafeHtml` by being escaped, user-supplied fragments by being
+///   sanitized, and anything else only through the explicit `SafeHtml::trusted` opt-out.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct SafeHtml(String);
+
+impl SafeHtml {
+    /// Escape text (any `Display` value) so that it renders literally
+    pub fn escape<T: Display + ?Sized>(text: &T) -> SafeHtml {
+        SafeHtml(escape(&text.to_string()))
+    }
+
+    /// Mark `html` as trusted without escaping it
+    ///
+    /// Never use this for markup that includes user input; see `SafeHtml::sanitize`.
+    pub fn trusted<S: Into<String>>(html: S) -> SafeHtml {
+        SafeHtml(html.into())
+    }
+
+    /// Sanitize an untrusted HTML fragment (see `html::sanitize`)
+    pub fn sanitize(html: &str) -> SafeHtml {
+        SafeHtml(sanitize(html))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl Display for SafeHtml {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Render any `Display` value as escaped text
+///
+/// ```ignore
+/// app.bind("#greeting", Text(name));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Text<T>(pub T);
+
+impl<T: Display> Display for Text<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&escape(&self.0.to_string()))
+    }
+}
+
+impl Renderable for SafeHtml {
+    fn render(&self, _node: &Node, _app: &AppContext) -> Result<String> {
+        Ok(self.0.clone())
+    }
+}
+
+impl<T: 'static + Display> Renderable for Text<T> {
+    fn render(&self, _node: &Node, _app: &AppContext) -> Result<String> {
+        Ok(self.to_string())
+    }
+}
+
+impl Renderable for String {
+    fn render(&self, _node: &Node, _app: &AppContext) -> Result<String> {
+        Ok(escape(self))
+    }
+}
+
+impl Renderable for &'static str {
+    fn render(&self, _node: &Node, _app: &AppContext) -> Result<String> {
+        Ok(escape(self))
+    }
+}
+
+// Static content can be bound directly
+impl Component for SafeHtml {
+    fn onload(_view: &View<Self>) {}
+}
+
+impl<T: 'static + Display> Component for Text<T> {
+    fn onload(_view: &View<Self>) {}
+}
+
+impl Component for String {
+    fn onload(_view: &View<Self>) {}
+}
+
+impl Component for &'static str {
+    fn onload(_view: &View<Self>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use webplatform;
+    use state::AppState;
+    use html::keyed;
+    use super::*;
+
+    #[test]
+    fn escapes_text_once() {
+        let safe = SafeHtml::escape("<b>Tom & Jerry</b>");
+        assert_eq!(safe.as_str(), "&lt;b&gt;Tom &amp; Jerry&lt;/b&gt;");
+        assert_eq!(safe.to_string(), safe.as_str());
+        assert_eq!(Text("a < b").to_string(), "a &lt; b");
+        assert_eq!(keyed("li", "1", &safe).as_str(),
+                   "<li q-key=\"1\">&lt;b&gt;Tom &amp; Jerry&lt;/b&gt;</li>");
+    }
+
+    #[test]
+    fn renders_safe_html_as_is_and_strings_escaped() {
+        let app = Rc::new(AppState::new(webplatform::init()));
+        let node = Node::new(app.clone(), Rc::new(app.document.element_create("div").unwrap()));
+        let context = AppContext::new(app, None);
+
+        let safe = SafeHtml::escape("a & b");
+        assert_eq!(safe.render(&node, &context).unwrap(), "a &amp; b");
+        assert_eq!(SafeHtml::trusted("<br>").render(&node, &context).unwrap(), "<br>");
+        assert_eq!(String::from("<br>").render(&node, &context).unwrap(), "&lt;br&gt;");
+        assert_eq!("a & b".render(&node, &context).unwrap(), "a &amp; b");
+        assert_eq!(Text(safe).render(&node, &context).unwrap(), "a &amp;amp; b");
+    }
+}
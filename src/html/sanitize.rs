@@ -0,0 +1,277 @@
+use super::escape;
+
+// Elements kept by `sanitize`, without any attributes except those in `ALLOWED_ATTRS`
+const ALLOWED_TAGS: &'static [&'static str] = &["a", "b", "blockquote", "br", "code", "em", "i", "li", "ol", "p",
+                                                "pre", "s", "small", "span", "strong", "sub", "sup", "u", "ul"];
+
+// Elements without content or a closing tag
+const VOID_TAGS: &'static [&'static str] = &["br"];
+
+// Elements dropped along with everything they contain
+const DROPPED_TAGS: &'static [&'static str] = &["script", "style", "iframe", "object", "embed", "noscript",
+                                                "template", "textarea", "title"];
+
+// (element, attribute) pairs kept by `sanitize`; URL attributes are checked by `is_safe_url`
+const ALLOWED_ATTRS: &'static [(&'static str, &'static str)] = &[("a", "href"), ("a", "title")];
+
+/// Sanitize an untrusted HTML fragment
+///
+/// Only a small set of formatting elements (e.g. `p`, `em`, `ul`, `a`) is kept, with attributes
+///   limited to `href` and `title` on links, where `href` must be relative or use http(s) or mailto.
+/// Other elements are removed, keeping their text (except for `script`, `style` and similar,
+///   whose content is removed too), comments are removed, and unclosed elements are closed.
+pub fn sanitize(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut open: Vec<&'static str> = vec![];
+    let mut dropping: Option<String> = None;
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        let start = match rest.find('<') {
+            Some(start) => start,
+            None => {
+                if dropping.is_none() {
+                    out.push_str(&escape_text(rest));
+                }
+                break;
+            }
+        };
+        if dropping.is_none() {
+            out.push_str(&escape_text(&rest[..start]));
+        }
+        rest = &rest[start..];
+
+        if rest.starts_with("<!--") {
+            rest = rest.find("-->").map(|end| &rest[end + 3..]).unwrap_or("");
+            continue;
+        }
+
+        let tag = match parse_tag(rest) {
+            Some((tag, len)) => {
+                rest = &rest[len..];
+                tag
+            }
+            None => {
+                // Not a tag, so the '<' is just text
+                if dropping.is_none() {
+                    out.push_str("&lt;");
+                }
+                rest = &rest[1..];
+                continue;
+            }
+        };
+
+        if let Some(dropped) = dropping.take() {
+            if !(tag.closing && tag.name == dropped) {
+                dropping = Some(dropped);
+            }
+            continue;
+        }
+
+        if DROPPED_TAGS.iter().any(|&dropped| dropped == tag.name) {
+            if !tag.closing && !tag.self_closing {
+                dropping = Some(tag.name);
+            }
+            continue;
+        }
+
+        let name = match ALLOWED_TAGS.iter().find(|&&allowed| allowed == tag.name) {
+            Some(name) => *name,
+            None => continue,
+        };
+
+        if tag.closing {
+            if let Some(pos) = open.iter().rposition(|&o| o == name) {
+                for unclosed in open.drain(pos..).rev() {
+                    out.push_str(&format!("</{}>", unclosed));
+                }
+            }
+            continue;
+        }
+
+        out.push('<');
+        out.push_str(name);
+        for (attr, value) in tag.attrs {
+            let allowed = ALLOWED_ATTRS.iter().any(|&(tag_name, attr_name)| tag_name == name && attr_name == attr);
+            if !allowed || (attr == "href" && !is_safe_url(&value)) {
+                continue;
+            }
+            out.push_str(&format!(" {}=\"{}\"", attr, escape(&value)));
+        }
+        out.push('>');
+        if !VOID_TAGS.contains(&name) {
+            open.push(name);
+        }
+    }
+
+    for unclosed in open.into_iter().rev() {
+        out.push_str(&format!("</{}>", unclosed));
+    }
+    out
+}
+
+struct Tag {
+    name: String,
+    closing: bool,
+    self_closing: bool,
+    attrs: Vec<(String, String)>,
+}
+
+// Parse the tag at the start of `src`, returning it along with its length
+fn parse_tag(src: &str) -> Option<(Tag, usize)> {
+    // Find the closing '>' outside of any quoted attribute value
+    let mut quote = None;
+    let mut end = None;
+    for (i, c) in src.char_indices().skip(1) {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '>') => {
+                end = Some(i);
+                break;
+            }
+            _ => (),
+        }
+    }
+    let end = end?;
+
+    let mut body = &src[1..end];
+    let closing = body.starts_with('/');
+    if closing {
+        body = &body[1..];
+    }
+    let self_closing = body.ends_with('/');
+
+    let name_len = body.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(body.len());
+    if name_len == 0 {
+        return None;
+    }
+    let tag = Tag {
+        name: body[..name_len].to_ascii_lowercase(),
+        closing: closing,
+        self_closing: self_closing,
+        attrs: parse_attrs(&body[name_len..]),
+    };
+    Some((tag, end + 1))
+}
+
+fn parse_attrs(mut src: &str) -> Vec<(String, String)> {
+    let mut attrs = vec![];
+    loop {
+        src = src.trim_left_matches(|c: char| c.is_whitespace() || c == '/');
+        if src.is_empty() {
+            return attrs;
+        }
+
+        let name_len = src.find(|c: char| c.is_whitespace() || c == '=' || c == '/').unwrap_or(src.len());
+        let name = src[..name_len].to_ascii_lowercase();
+        src = src[name_len..].trim_left();
+
+        let mut value = String::new();
+        if src.starts_with('=') {
+            src = src[1..].trim_left();
+            let (raw, next) = match src.chars().next() {
+                Some(q) if q == '"' || q == '\'' => {
+                    let end = src[1..].find(q).map(|i| i + 1).unwrap_or(src.len());
+                    (&src[1..end], (end + 1).min(src.len()))
+                }
+                _ => {
+                    let end = src.find(char::is_whitespace).unwrap_or(src.len());
+                    (&src[..end], end)
+                }
+            };
+            value = raw.to_owned();
+            src = &src[next..];
+        }
+
+        if !name.is_empty() {
+            attrs.push((name, value));
+        }
+    }
+}
+
+// Relative URLs, or absolute URLs using a scheme that cannot run script
+fn is_safe_url(url: &str) -> bool {
+    // Browsers ignore whitespace and control characters within a scheme (e.g. "java\tscript:")
+    let url: String = url.chars().filter(|&c| c > ' ').collect::<String>().to_ascii_lowercase();
+    match url.find(|c| c == ':' || c == '/' || c == '?' || c == '#') {
+        Some(i) if url[i..].starts_with(':') => {
+            let scheme = &url[..i];
+            scheme == "http" || scheme == "https" || scheme == "mailto"
+        }
+        _ => true,
+    }
+}
+
+// Escape text, leaving character references (e.g. `&amp;` or `&#8212;`) intact
+fn escape_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for (i, part) in text.split('&').enumerate() {
+        if i > 0 {
+            let entity_len = part.find(';').unwrap_or(0);
+            let is_entity = entity_len > 0 &&
+                            part[..entity_len].chars().enumerate().all(|(j, c)| {
+                c.is_ascii_alphanumeric() || (j == 0 && c == '#')
+            });
+            out.push_str(if is_entity { "&" } else { "&amp;" });
+        }
+        out.push_str(&escape(part));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_script_urls() {
+        assert!(!is_safe_url("javascript:alert(1)"));
+        assert!(!is_safe_url("JaVaScRiPt:alert(1)"));
+        assert!(!is_safe_url("java\tscript:alert(1)"));
+        assert!(!is_safe_url("java\nscript:alert(1)"));
+        assert!(!is_safe_url("  \u{1}javascript:alert(1)"));
+        assert!(!is_safe_url("data:text/html,<script>alert(1)</script>"));
+        assert!(!is_safe_url("vbscript:msgbox(1)"));
+    }
+
+    #[test]
+    fn accepts_relative_and_web_urls() {
+        assert!(is_safe_url("https://example.com/a?b=c:d"));
+        assert!(is_safe_url("HTTP://example.com"));
+        assert!(is_safe_url("mailto:someone@example.com"));
+        assert!(is_safe_url("/path/to:page"));
+        assert!(is_safe_url("page?query=a:b"));
+        assert!(is_safe_url("#section"));
+    }
+
+    #[test]
+    fn strips_event_handler_attributes() {
+        assert_eq!(sanitize("<p onclick=\"steal()\">hi</p>"), "<p>hi</p>");
+        assert_eq!(sanitize("<a href='/x' ONMOUSEOVER=steal() title=\"t\">link</a>"),
+                   "<a href=\"/x\" title=\"t\">link</a>");
+        assert_eq!(sanitize("<a href=\"java\tscript:steal()\">link</a>"), "<a>link</a>");
+    }
+
+    #[test]
+    fn drops_scripts_and_styles_with_their_content() {
+        assert_eq!(sanitize("a<script>alert(\"<p>\")</script>b<style>p { color: red }</style>c"), "abc");
+        assert_eq!(sanitize("<SCRIPT src=x></SCRIPT>ok"), "ok");
+        assert_eq!(sanitize("<div><img src=x onerror=steal()>text</div>"), "text");
+    }
+
+    #[test]
+    fn closes_unclosed_tags() {
+        assert_eq!(sanitize("<p><em>hi"), "<p><em>hi</em></p>");
+        assert_eq!(sanitize("<ul><li>a</ul>"), "<ul><li>a</li></ul>");
+        assert_eq!(sanitize("a</p><br>b"), "a<br>b");
+    }
+
+    #[test]
+    fn escapes_text_but_keeps_character_references() {
+        assert_eq!(sanitize("1 < 2 > 0"), "1 &lt; 2 &gt; 0");
+        assert_eq!(escape_text("a &amp; b &#8212; & c"), "a &amp; b &#8212; &amp; c");
+        assert_eq!(sanitize("<!-- <script> -->\"quoted\""), "&quot;quoted&quot;");
+    }
+}
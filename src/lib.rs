@@ -1,19 +1,55 @@
 extern crate webplatform;
 extern crate rustc_serialize;
 extern crate uuid;
+extern crate quasar_template;
+extern crate futures;
+#[cfg(feature = "mustache")]
+extern crate mustache;
+#[cfg(feature = "maud")]
+extern crate maud;
+#[cfg(feature = "derive")]
+extern crate quasar_derive;
 
 #[macro_use]
 extern crate downcast_rs;
+#[macro_use]
+extern crate quick_error;
+#[macro_use]
+extern crate log;
 
+#[macro_use]
+mod trace;
+mod error;
+mod platform;
 mod events;
 mod components;
+mod form;
 mod state;
 mod node;
 mod view;
+mod task;
+mod timer;
+mod executor;
+mod css;
+mod dom;
+pub mod fetch;
+pub mod ws;
 mod app;
+pub mod template;
+pub mod html;
 
-pub use events::EventType;
-pub use components::{Properties, Renderable};
+pub use error::{Error, Result};
+pub use events::{EventType, ItemKey};
+pub use task::TaskHandle;
+pub use timer::{Timers, BrowserTimers, ManualTimers};
+pub use fetch::FetchState;
+pub use form::{Form, FormErrors, decode_form};
+pub use template::Template;
+pub use html::{SafeHtml, Text};
+pub use trace::TraceRecord;
+pub use components::{Properties, Renderable, Snapshot, fingerprint};
+pub use css::scope_attr;
+pub use dom::{StyleValue, Px, Em, Percent, PropValue, FileInfo};
 pub use app::{init, QuasarApp, AppContext};
 pub use node::Node;
 pub use view::View;
@@ -24,6 +60,12 @@ use std::cell::{Ref, RefMut};
 
 #[cfg(feature = "mustache")]
 pub use mustache::compile_str;
+#[cfg(feature = "mustache")]
+pub use components::{RuntimeComponent, RuntimeData};
+#[cfg(feature = "maud")]
+pub use html::maud::RenderMarkup;
+#[cfg(feature = "derive")]
+pub use quasar_derive::Template;
 
 
 impl<'doc> QuasarApp<'doc> {
@@ -84,25 +126,125 @@ fn lookup_props<'doc>(node: &HtmlNode<'doc>, keys: &[&'static str]) -> Propertie
 }
 
 pub trait Component: Renderable + Sized {
+    /// Called once after the component is first bound and rendered
     fn onload(view: &View<Self>);
+
+    /// Names of the node properties (or attributes) this component reads as props
+    ///
+    /// These are compared each time the view renders to decide whether to call `on_props_changed`.
+    fn props(&self) -> &[&'static str] {
+        &[]
+    }
+
+    /// Decide whether a queued rerender of the view should happen
+    ///
+    /// This is not consulted for the initial render when binding.
+    fn should_render(_view: &View<Self>) -> bool {
+        true
+    }
+
+    /// Opt-in change detection for the component data
+    ///
+    /// When this returns `Some`, a queued rerender is skipped if the value is unchanged since
+    ///   the last render, unless props or observed app data changed too.
+    ///   `quasar::fingerprint(self)` provides a suitable value for `Hash` types.
+    fn fingerprint(&self) -> Option<u64> {
+        None
+    }
+
+    /// Opt-in change detection for component data that is `Clone + PartialEq`
+    ///
+    /// Like `fingerprint`, but the copy returned by `Snapshot::of(self)` is compared exactly,
+    ///   at the cost of cloning the data after every render.
+    fn snapshot(&self) -> Option<Snapshot> {
+        None
+    }
+
+    /// Called before every render of the view, including the initial render when binding
+    fn before_render(_view: &View<Self>) {}
+
+    /// Called after every render of the view once the DOM has been patched
+    fn after_render(_view: &View<Self>) {}
+
+    /// Called before rendering when any of the props named by `props` changed since the last render
+    fn on_props_changed(_view: &View<Self>, _props: &Properties) {}
+
+    /// Called when the view is unbound, before it stops receiving events or being rendered
+    fn on_unmount(_view: &View<Self>) {}
+
+    /// A stylesheet scoped to views of this component type
+    ///
+    /// It is injected into `<head>` when the first view of this type is bound, with its selectors
+    ///   rewritten to only match the view's rendered elements (see `quasar::scope_attr`).
+    fn styles() -> Option<&'static str> {
+        None
+    }
+
+    /// Named handlers that rendered markup attaches with `q-on:<event>="name"` attributes
+    ///
+    /// For example, `<button q-on:click="increment">+1</button>` calls the handler
+    ///   named "increment" when clicked, including on elements added by later renders:
+    ///
+    /// ```ignore
+    /// fn handlers() -> Vec<(&'static str, NamedHandler<Self>)> {
+    ///     vec![("increment", CounterData::increment)]
+    /// }
+    /// ```
+    fn handlers() -> Vec<(&'static str, NamedHandler<Self>)> {
+        vec![]
+    }
 }
 
+/// A handler that markup refers to by name (see `Component::handlers`)
+pub type NamedHandler<R> = for<'e, 'doc> fn(Event<'e, View<'doc, R>>);
+
 
 pub trait Queryable<'doc> {
     type Q: Queryable<'doc>;
 
     fn query(&self, el: &str) -> Option<Self::Q>;
-    // fn query_all(&self, el: &str) -> Vec<Self>
 
-    fn bind<R>(&self, el: &str, component: R) where R: 'static + Component;
+    /// Query for every element matching `el`, in document order
+    fn query_all(&self, el: &str) -> Vec<Self::Q>;
+
+    /// Query for an element, returning `Error::ElementNotFound` if nothing matches
+    fn try_query(&self, el: &str) -> Result<Self::Q> {
+        self.query(el).ok_or_else(|| Error::ElementNotFound(el.to_owned()))
+    }
+
+    /// Bind a component to the first element matching `el`
+    ///
+    /// Returns an error if no element matches or if the initial render fails.
+    fn try_bind<R>(&self, el: &str, component: R) -> Result<View<'doc, R>> where R: 'static + Component;
+
+    /// Bind a component to the first element matching `el`
+    ///
+    /// Panics if the component cannot be bound; see `try_bind`.
+    fn bind<R>(&self, el: &str, component: R) -> View<'doc, R>
+        where R: 'static + Component
+    {
+        self.try_bind(el, component)
+            .unwrap_or_else(|err| panic!("Failed to bind '{}': {}", el, err))
+    }
     // fn bind_each(&self, el: &str, component: Vec<R>) -> BindEachNode<'doc, R>;
 }
 
 pub trait HasBind<'doc> {
     type R: Renderable;
 
-    fn data(&self) -> Ref<Self::R>;
-    fn data_mut(&mut self) -> RefMut<Self::R>;
+    /// Get the bound component data, returning an error if it is mutably borrowed
+    fn try_data(&self) -> Result<Ref<Self::R>>;
+
+    /// Get the bound component data mutably, returning an error if it is already borrowed
+    fn try_data_mut(&mut self) -> Result<RefMut<Self::R>>;
+
+    fn data(&self) -> Ref<Self::R> {
+        self.try_data().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    fn data_mut(&mut self) -> RefMut<Self::R> {
+        self.try_data_mut().unwrap_or_else(|err| panic!("{}", err))
+    }
 }
 
 
@@ -114,6 +256,13 @@ pub struct Event<'doc, N> {
     pub binding: N,
     // The globally shared app context (provides access to document root)
     pub app: AppContext<'doc>,
-    // The target's index offset when event was attached multiple times for a selector
+    /// The element the handler was attached to, e.g. the element matching the selector of `on_each`
+    pub matched: Node<'doc>,
+    /// The key of the item `matched` renders, if it or an ancestor within the view has one (see `ItemKey`)
+    pub key: Option<ItemKey>,
+    /// The position of `matched` among the elements matching the selector when the handler was attached
+    ///
+    /// This is not updated when rerendering reorders or filters the elements, so prefer `key`
+    ///   to identify items.
     pub index: usize,
 }
\ No newline at end of file
@@ -1,43 +1,35 @@
-use state::{AppState, TypedKey};
+use state::AppState;
 use std::rc::Rc;
 use webplatform::{self, HtmlNode};
-use uuid::Uuid;
+use view::bind_component;
+use dom::{self, StyleValue, PropValue, FileInfo};
+use platform;
+use events::item_key;
 
-use {Queryable, Component, Properties, Event, EventType, View, AppContext, lookup_props};
+use {Queryable, Component, Properties, Event, EventType, View, AppContext, Error, Result, TraceRecord, lookup_props};
 
+#[derive(Clone)]
 pub struct Node<'doc> {
     app: Rc<AppState<'doc>>,
     node: Rc<HtmlNode<'doc>>,
 }
 
-
 impl<'doc> Queryable<'doc> for Node<'doc> {
     type Q = Self;
 
     fn query(&self, el: &str) -> Option<Self::Q> {
-        self.node.element_query(el).map(|node| {
-            Node {
-                app: self.app.clone(),
-                node: Rc::new(node),
-            }
-        })
+        self.node.element_query(el).map(|node| self.wrap(node))
     }
 
-    fn bind<RR>(&self, el: &str, component: RR)
+    fn query_all(&self, el: &str) -> Vec<Self::Q> {
+        self.node.element_query_all(el).into_iter().map(|node| self.wrap(node)).collect()
+    }
+
+    fn try_bind<RR>(&self, el: &str, component: RR) -> Result<View<'doc, RR>>
         where RR: 'static + Component
     {
-        let node = self.node.element_query(el).expect("querySelector found no results");
-        let rc_node = Rc::new(node);
-
-        let render_node = Node { app: self.app.clone(), node: rc_node.clone() };
-        let key = Uuid::new_v4().to_string();
-
-        let app_context = AppContext::new(self.app.clone(), Some(TypedKey::new::<RR>(&key)));
-        rc_node.html_patch(&component.render(&render_node, &app_context));
-
-        let binding = self.app.insert_binding(&key, component, rc_node.clone());
-        let view = View::new(self.app.clone(), rc_node, key, binding);
-        RR::onload(&view);
+        let node = self.node.element_query(el).ok_or_else(|| Error::ElementNotFound(el.to_owned()))?;
+        bind_component(&self.app, node, component)
     }
 }
 
@@ -50,49 +42,41 @@ impl<'doc> Node<'doc> {
         }
     }
 
-    pub fn query_all(&self, el: &str) -> Vec<Node<'doc>> {
-        self.node.element_query_all(el).into_iter().map(|node| {
-            Node {
-                app: self.app.clone(),
-                node: Rc::new(node),
-            }
-        }).collect()
-    }
-
     pub fn on<F>(&self, event: EventType, f: F)
         where F: Fn(Event<Self>) + 'doc
     {
         let app = self.app.clone();
         let node = self.node.clone();
 
+        let event_name = event.name();
+
         let event_handler = Rc::new(move |evt: webplatform::Event<'doc>, i| {
             let node = Node {
                 app: app.clone(),
                 node: node.clone(),
             };
-            println!("Event fired on {:?} for target {:?}",
-                     &node.node,
-                     evt.target);
+            app.trace(TraceRecord::EventFired { event: event_name, view: None });
             let target_node = evt.target.expect("Event did not have a target");
             let event = Event {
                 app: AppContext::new(app.clone(), None),
+                key: item_key(&node.node, &node.node),
+                matched: node.clone(),
                 binding: node,
                 target: Node {
                     node: Rc::new(target_node),
                     app: app.clone(),
                 },
-                // FIXME: strange to attach a meaningless index here
                 index: i,
             };
             f(event);
-            app.process_render_queue();
+            app.flush_render_queue();
         });
 
         // Attach event_handler to the DOM
         let f = event_handler.clone();
 
         self.node.on(event.name(), move |evt| f(evt, 0));
-        println!("On handler registered for {:?}", self.node);
+        self.app.trace(TraceRecord::HandlerRegistered { event: event.name(), selector: None, count: 1 });
     }
 
     pub fn set(&self, prop: &str, value: &str) {
@@ -107,16 +91,177 @@ impl<'doc> Node<'doc> {
         self.node.attr_get_str(attr)
     }
 
+    pub fn set_attr(&self, attr: &str, value: &str) {
+        self.node.attr_set_str(attr, value);
+    }
+
+    pub fn remove_attr(&self, attr: &str) {
+        platform::remove_attr(&self.node, attr);
+    }
+
+    pub fn has_class(&self, class: &str) -> bool {
+        dom::has_class(&self.node, class)
+    }
+
+    pub fn add_class(&self, class: &str) {
+        dom::add_class(&self.node, class)
+    }
+
+    pub fn remove_class(&self, class: &str) {
+        dom::remove_class(&self.node, class)
+    }
+
+    /// Add the class if missing, or remove it if present, returning whether it is now present
+    pub fn toggle_class(&self, class: &str) -> bool {
+        dom::toggle_class(&self.node, class)
+    }
+
+    /// Get an inline style property, e.g. `node.style("display")`
+    pub fn style(&self, name: &str) -> Option<String> {
+        dom::style(&self.node, name)
+    }
+
+    /// Set an inline style property, e.g. `node.set_style("width", Px(120.0))`
+    pub fn set_style<V: StyleValue>(&self, name: &str, value: V) {
+        dom::set_style(&self.node, name, &value.css_value())
+    }
+
+    pub fn remove_style(&self, name: &str) {
+        dom::remove_style(&self.node, name)
+    }
+
     pub fn get_properties(&self, keys: &[&'static str]) -> Properties {
         lookup_props(&self.node, keys)
     }
 
+    /// Get a DOM property as a `String`, `bool`, `i32` or `f64`, e.g. `node.get_prop::<bool>("disabled")`
+    pub fn get_prop<T: PropValue>(&self, prop: &str) -> T {
+        T::get_prop(&self.node, prop)
+    }
+
+    pub fn set_prop<T: PropValue>(&self, prop: &str, value: T) {
+        value.set_prop(&self.node, prop)
+    }
+
     pub fn checked(&self) -> bool {
-        self.node.prop_get_i32("checked") != 0
+        self.get_prop("checked")
+    }
+
+    pub fn set_checked(&self, checked: bool) {
+        self.set_prop("checked", checked)
     }
 
     pub fn value(&self) -> String {
         self.get("value")
     }
 
-}
\ No newline at end of file
+    /// The value of a number, range or date input, or `None` if empty or invalid
+    pub fn value_as_number(&self) -> Option<f64> {
+        let value: f64 = self.get_prop("valueAsNumber");
+        if value.is_nan() { None } else { Some(value) }
+    }
+
+    /// The index of the selected option of a `<select>`, or `None` if nothing is selected
+    pub fn selected_index(&self) -> Option<usize> {
+        let index: i32 = self.get_prop("selectedIndex");
+        if index < 0 { None } else { Some(index as usize) }
+    }
+
+    pub fn set_selected_index(&self, index: Option<usize>) {
+        self.set_prop("selectedIndex", index.map(|i| i as i32).unwrap_or(-1))
+    }
+
+    /// The values of all selected options of a `<select>`, including one with `multiple`
+    pub fn selected_values(&self) -> Vec<String> {
+        self.query_all("option")
+            .into_iter()
+            .filter(|option| option.get_prop("selected"))
+            .map(|option| option.value())
+            .collect()
+    }
+
+    /// The value of the checked radio button named `name` within this element (e.g. a form)
+    pub fn radio_value(&self, name: &str) -> Option<String> {
+        self.query_all("input[type=radio]")
+            .into_iter()
+            .find(|radio| radio.checked() && radio.get_attr("name") == name)
+            .map(|radio| radio.value())
+    }
+
+    /// Metadata of the files chosen with an `<input type="file">`
+    pub fn files(&self) -> Vec<FileInfo> {
+        platform::files(&self.node).into_iter().map(|(name, size, mime_type)| {
+            FileInfo {
+                name: name,
+                size: size,
+                mime_type: mime_type,
+            }
+        }).collect()
+    }
+
+    /// The parent element of this element
+    pub fn parent(&self) -> Option<Node<'doc>> {
+        self.node.parent().map(|node| self.wrap(node))
+    }
+
+    /// The child elements of this element, in document order
+    pub fn children(&self) -> Vec<Node<'doc>> {
+        dom::children(&self.node).into_iter().map(|node| self.wrap(node)).collect()
+    }
+
+    pub fn next_sibling(&self) -> Option<Node<'doc>> {
+        dom::sibling(&self.node, 1).map(|node| self.wrap(node))
+    }
+
+    pub fn prev_sibling(&self) -> Option<Node<'doc>> {
+        dom::sibling(&self.node, -1).map(|node| self.wrap(node))
+    }
+
+    /// The nearest element matching `selector`, starting with this element itself and walking up its ancestors
+    pub fn closest(&self, selector: &str) -> Option<Node<'doc>> {
+        dom::closest(&self.node, selector).map(|node| Node::new(self.app.clone(), node))
+    }
+
+    /// Whether this element matches `selector`
+    pub fn matches(&self, selector: &str) -> bool {
+        dom::matches(&self.node, selector)
+    }
+
+    /// Create a detached element (see `QuasarApp::create_element`)
+    pub fn create_element(&self, tag: &str) -> Result<Node<'doc>> {
+        self.app.create_element(tag)
+    }
+
+    /// Append `child` as the last child of this element, moving it if it is already in the document
+    ///
+    /// Note that rerendering a view replaces its content, including any nodes inserted into it.
+    pub fn append_child(&self, child: &Node<'doc>) {
+        self.node.append(&child.node);
+    }
+
+    /// Insert `child` into this element before `reference`, which must be a child of this element
+    pub fn insert_before(&self, child: &Node<'doc>, reference: &Node<'doc>) {
+        platform::insert_before(&self.node, &child.node, &reference.node);
+    }
+
+    /// Remove this element from the document
+    pub fn remove(&self) {
+        self.node.remove_self();
+    }
+
+    /// Replace this element in the document with `other`, which is a no-op if it has no parent
+    pub fn replace_with(&self, other: &Node<'doc>) {
+        if let Some(parent) = self.node.parent() {
+            platform::insert_before(&parent, &other.node, &self.node);
+            self.node.remove_self();
+        }
+    }
+
+    // Another node of the same app
+    fn wrap(&self, node: HtmlNode<'doc>) -> Node<'doc> {
+        Node {
+            app: self.app.clone(),
+            node: Rc::new(node),
+        }
+    }
+}
@@ -0,0 +1,309 @@
+//! Browser APIs beyond those webplatform provides
+//!
+//! Like webplatform's own bindings, these run inline JavaScript through emscripten.
+//!   Nodes are handed to a snippet with `select`, and snippets call back into Rust
+//!   through `Module.quasar.call` with the id of a registered callback, an integer and a string.
+//!
+//! Callbacks stay on the Rust side, in a table keyed by those ids, so only integers cross into
+//!   JavaScript. Anything that may be called back asynchronously must be `'static`.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+use std::rc::Rc;
+use rustc_serialize::json;
+use webplatform::HtmlNode;
+
+extern "C" {
+    fn emscripten_asm_const_int(code: *const c_char, ...) -> c_int;
+}
+
+// Run a JavaScript snippet, which refers to its integer (or pointer) arguments as $0, $1, ...
+macro_rules! js {
+    ($code:expr $(, $arg:expr)*) => {
+        unsafe { emscripten_asm_const_int(concat!($code, "\0").as_ptr() as *const c_char $(, $arg as c_int)*) }
+    };
+}
+
+type Callback = Rc<RefCell<Box<FnMut(i32, String)>>>;
+
+thread_local! {
+    static INITIALIZED: Cell<bool> = Cell::new(false);
+    static NEXT_CALLBACK: Cell<c_int> = Cell::new(1);
+    // Callbacks of pending snippets by id, until released with `Module.quasar.drop`
+    static CALLBACKS: RefCell<HashMap<c_int, Callback>> = RefCell::new(HashMap::new());
+}
+
+// Define the helpers shared by the snippets, unless already defined
+fn init() {
+    if INITIALIZED.with(|initialized| initialized.replace(true)) {
+        return;
+    }
+    js!("Module.quasar = { \
+           selected: [], \
+           timers: {}, \
+           sockets: {}, \
+           nextSocket: 1, \
+           call: function(id, arg, text) { \
+             var sp = Runtime.stackSave(); \
+             var ptr = text == null ? 0 : allocate(intArrayFromString(text), 'i8', ALLOC_STACK); \
+             Runtime.dynCall('viii', $0, [id, arg, ptr]); \
+             Runtime.stackRestore(sp); \
+           }, \
+           drop: function(id) { Runtime.dynCall('vi', $1, [id]); } \
+         }; \
+         Object.defineProperty(Node.prototype, '__quasarSelect', { \
+           set: function() { Module.quasar.selected.push(this); }, \
+           configurable: true \
+         });",
+        call_callback as *const (),
+        drop_callback as *const ());
+}
+
+// Queue `node` for the next snippet, which takes the queue with `Module.quasar.selected.splice(0)`
+//
+// webplatform doesn't expose its references to nodes, so this sets a property
+//   whose setter (defined by `init`) records the node it was set on.
+fn select(node: &HtmlNode) {
+    init();
+    node.prop_set_i32("__quasarSelect", 0);
+}
+
+fn c_str(s: &str) -> CString {
+    CString::new(s.replace('\0', "")).expect("interior nul bytes were removed")
+}
+
+// Register `f` for a snippet to call with `Module.quasar.call`, until it releases `f` with `Module.quasar.drop`
+fn callback<F: FnMut(i32, String) + 'static>(f: F) -> c_int {
+    init();
+    let id = NEXT_CALLBACK.with(|next| {
+        let id = next.get();
+        next.set(id.wrapping_add(1).max(1));
+        id
+    });
+    let callback: Callback = Rc::new(RefCell::new(Box::new(f)));
+    CALLBACKS.with(|callbacks| callbacks.borrow_mut().insert(id, callback));
+    id
+}
+
+extern "C" fn call_callback(id: c_int, arg: c_int, text: *const c_char) {
+    // Clone the callback out of the table while calling it, since the call may release it
+    let callback = match CALLBACKS.with(|callbacks| callbacks.borrow().get(&id).cloned()) {
+        Some(callback) => callback,
+        None => return,
+    };
+    let text = if text.is_null() {
+        String::new()
+    } else {
+        unsafe { CStr::from_ptr(text) }.to_string_lossy().into_owned()
+    };
+    (&mut *callback.borrow_mut())(arg, text);
+}
+
+extern "C" fn drop_callback(id: c_int) {
+    CALLBACKS.with(|callbacks| callbacks.borrow_mut().remove(&id));
+}
+
+/// Prevent the default action of `event` events on the elements within `root` matching `selector`
+///
+/// The listener is attached to `root`, so it covers matching elements added after it.
+pub fn prevent_default(root: &HtmlNode, event: &str, selector: &str) {
+    let event = c_str(event);
+    let selector = c_str(selector);
+    select(root);
+    js!("var root = Module.quasar.selected.splice(0)[0]; \
+         var selector = UTF8ToString($1); \
+         root.addEventListener(UTF8ToString($0), function(e) { \
+           if (e.target !== root && e.target.matches && e.target.matches(selector)) { \
+             e.preventDefault(); \
+           } \
+         });",
+        event.as_ptr(),
+        selector.as_ptr());
+}
+
+/// Call `f` once after `ms` milliseconds, returning an id for `clear_timer`
+pub fn set_timeout<F: FnOnce() + 'static>(ms: u32, f: F) -> i32 {
+    let mut f = Some(f);
+    let callback = callback(move |_, _| {
+        if let Some(f) = f.take() {
+            f();
+        }
+    });
+    js!("var q = Module.quasar; \
+         var id = setTimeout(function() { \
+           delete q.timers[id]; \
+           q.call($0, 0, null); \
+           q.drop($0); \
+         }, $1); \
+         q.timers[id] = function() { clearTimeout(id); q.drop($0); }; \
+         return id;",
+        callback,
+        ms)
+}
+
+/// Call `f` every `ms` milliseconds, returning an id for `clear_timer`
+pub fn set_interval<F: FnMut() + 'static>(ms: u32, mut f: F) -> i32 {
+    let callback = callback(move |_, _| f());
+    js!("var q = Module.quasar; \
+         var id = setInterval(function() { q.call($0, 0, null); }, $1); \
+         q.timers[id] = function() { clearInterval(id); q.drop($0); }; \
+         return id;",
+        callback,
+        ms)
+}
+
+/// Cancel a timeout or interval, which is a no-op once a timeout has fired
+pub fn clear_timer(id: i32) {
+    js!("var q = Module.quasar; \
+         var clear = q.timers[$0]; \
+         if (clear) { \
+           delete q.timers[$0]; \
+           clear(); \
+         }",
+        id);
+}
+
+/// Send an HTTP request, calling `done` with the status and text of the response
+///
+/// Network failures (including blocked cross-origin requests) are reported as status 0.
+pub fn xhr<F: FnOnce(u16, String) + 'static>(method: &str,
+                                              url: &str,
+                                              headers: &[(String, String)],
+                                              body: Option<&str>,
+                                              done: F) {
+    let mut done = Some(done);
+    let callback = callback(move |status, text| {
+        if let Some(done) = done.take() {
+            done(status as u16, text);
+        }
+    });
+    let method = c_str(method);
+    let url = c_str(url);
+    let headers = c_str(&json::encode(&headers).expect("headers are always encodable"));
+    let body = body.map(c_str);
+    js!("var q = Module.quasar; \
+         var done = function(status, text) { q.call($4, status, text); q.drop($4); }; \
+         var xhr = new XMLHttpRequest(); \
+         xhr.open(UTF8ToString($0), UTF8ToString($1)); \
+         JSON.parse(UTF8ToString($2)).forEach(function(header) { \
+           xhr.setRequestHeader(header[0], header[1]); \
+         }); \
+         xhr.onload = function() { done(xhr.status, xhr.responseText); }; \
+         xhr.onerror = xhr.onabort = function() { done(0, null); }; \
+         xhr.send($3 ? UTF8ToString($3) : null);",
+        method.as_ptr(),
+        url.as_ptr(),
+        headers.as_ptr(),
+        body.as_ref().map_or(ptr::null(), |body| body.as_ptr()),
+        callback);
+}
+
+/// An event of a socket opened with `websocket`
+pub enum SocketEvent {
+    Open,
+    Message(String),
+    /// The connection closed or failed to open, after which no more events are delivered
+    Close,
+}
+
+/// An open (or opening) WebSocket
+pub struct WebSocket {
+    id: i32,
+}
+
+/// Open a WebSocket to `url`, calling `on_event` with its events
+pub fn websocket<F: FnMut(SocketEvent) + 'static>(url: &str, mut on_event: F) -> WebSocket {
+    let callback = callback(move |kind, text| {
+        on_event(match kind {
+            0 => SocketEvent::Open,
+            1 => SocketEvent::Message(text),
+            _ => SocketEvent::Close,
+        })
+    });
+    let url = c_str(url);
+    let id = js!("var q = Module.quasar; \
+                  var close = function() { q.call($1, 2, null); q.drop($1); }; \
+                  try { \
+                    var socket = new WebSocket(UTF8ToString($0)); \
+                  } catch (e) { \
+                    setTimeout(close, 0); \
+                    return 0; \
+                  } \
+                  var id = q.nextSocket++; \
+                  q.sockets[id] = socket; \
+                  socket.onopen = function() { q.call($1, 0, null); }; \
+                  socket.onmessage = function(e) { q.call($1, 1, String(e.data)); }; \
+                  socket.onclose = function() { delete q.sockets[id]; close(); }; \
+                  return id;",
+                 url.as_ptr(),
+                 callback);
+    WebSocket { id: id }
+}
+
+impl WebSocket {
+    pub fn send(&self, message: &str) {
+        let message = c_str(message);
+        js!("var socket = Module.quasar.sockets[$0]; \
+             if (socket) { socket.send(UTF8ToString($1)); }",
+            self.id,
+            message.as_ptr());
+    }
+
+    pub fn close(&self) {
+        js!("var socket = Module.quasar.sockets[$0]; \
+             if (socket) { socket.close(); }",
+            self.id);
+    }
+}
+
+/// Remove the attribute `attr` from `node`, if present
+pub fn remove_attr(node: &HtmlNode, attr: &str) {
+    let attr = c_str(attr);
+    select(node);
+    js!("Module.quasar.selected.splice(0)[0].removeAttribute(UTF8ToString($0));",
+        attr.as_ptr());
+}
+
+/// Whether `node` is an element matching `selector`, which is false for invalid selectors
+pub fn matches(node: &HtmlNode, selector: &str) -> bool {
+    let selector = c_str(selector);
+    select(node);
+    js!("var node = Module.quasar.selected.splice(0)[0]; \
+         try { \
+           return node.matches ? node.matches(UTF8ToString($0)) : 0; \
+         } catch (e) { \
+           return 0; \
+         }",
+        selector.as_ptr()) != 0
+}
+
+/// Insert `child` into `parent` before `reference`
+pub fn insert_before(parent: &HtmlNode, child: &HtmlNode, reference: &HtmlNode) {
+    select(parent);
+    select(child);
+    select(reference);
+    js!("var nodes = Module.quasar.selected.splice(0); \
+         nodes[0].insertBefore(nodes[1], nodes[2]);");
+}
+
+/// The name, size and MIME type of each file chosen with a file input
+pub fn files(node: &HtmlNode) -> Vec<(String, u64, String)> {
+    let files = Rc::new(RefCell::new(String::new()));
+    let callback = {
+        let files = files.clone();
+        callback(move |_, text| *files.borrow_mut() = text)
+    };
+    select(node);
+    js!("var q = Module.quasar; \
+         var files = Array.prototype.map.call(q.selected.splice(0)[0].files || [], function(file) { \
+           return [file.name, file.size, file.type]; \
+         }); \
+         q.call($0, 0, JSON.stringify(files)); \
+         q.drop($0);",
+        callback);
+    let files = files.borrow().clone();
+    json::decode(&files).expect("file metadata is encoded by the snippet")
+}
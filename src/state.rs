@@ -1,38 +1,94 @@
 use std::collections::{HashMap, HashSet};
 use std::any::{Any, TypeId};
-use std::cell::{RefCell, Ref, RefMut};
+use std::cell::RefCell;
 use std::rc::Rc;
 use std::ops::{Deref, DerefMut};
+use std::marker::PhantomData;
 use webplatform::{self, Document, HtmlNode};
 
-use {AppContext, EventType, Renderable, Node};
+use std::mem;
+use view::dispatch_lifecycle;
+
+use {AppContext, Component, EventType, Properties, Renderable, Snapshot, Node, lookup_props};
+use error::{Error, Result};
+use trace::TraceRecord;
+use html;
+use css;
+use dom;
+use task::TaskHandle;
+use timer::{Timers, BrowserTimers};
+use fetch::{Transport, XhrTransport};
+use ws::{SocketTransport, BrowserSocketTransport};
+use executor::{self, Executor};
+
+// Called with the event, the element's position among the selector matches when registered,
+//   and the matched element
+pub type HandlerFn<'doc> = Rc<Fn(webplatform::Event<'doc>, usize, Rc<HtmlNode<'doc>>) + 'doc>;
 
 pub struct Handler<'doc> {
     el: Option<String>,
     event_type: EventType,
-    event_handler: Rc<Fn(webplatform::Event<'doc>, usize) + 'doc>,
+    event_handler: HandlerFn<'doc>,
     registered_nodes: RefCell<Vec<Rc<HtmlNode<'doc>>>>,
 }
 
+/// Stage of a binding's lifecycle, dispatched to the typed `Component` hooks
+pub enum Lifecycle {
+    ShouldRender,
+    BeforeRender,
+    AfterRender,
+    Unmount,
+}
+
+// Writes component data back into the DOM after every render (e.g. two-way bound input values)
+pub type SyncFn<'doc> = Rc<Fn(&mut Binding<'doc>) + 'doc>;
+
+// Dispatches a lifecycle stage to the hooks of the bound component type,
+//   which is erased once the component is boxed into the binding.
+// Returns false only when the `ShouldRender` stage vetoes rendering.
+type LifecycleFn<'doc> = fn(&Rc<AppState<'doc>>, &TypedKey, &Rc<RefCell<Binding<'doc>>>, Lifecycle) -> bool;
+
 pub struct Binding<'doc> {
     pub node: Rc<HtmlNode<'doc>>,
+    pub props: Properties,
+    pub fingerprint: Option<u64>,
+    pub snapshot: Option<Snapshot>,
     component: Box<Renderable>,
     handlers: Vec<Handler<'doc>>,
+    syncs: Vec<SyncFn<'doc>>,
+    lifecycle: LifecycleFn<'doc>,
+    last_render: Option<String>,
+    refs: HashMap<String, Rc<HtmlNode<'doc>>>,
+    children: Vec<TypedKey>,
+    named_events: Vec<&'static str>,
+    // Attribute added to every rendered element when the component has styles
+    scope: Option<String>,
 }
 
 impl<'doc> Binding<'doc> {
-    pub fn new<R: 'static + Renderable>(component: R, node: Rc<HtmlNode<'doc>>) -> Binding<'doc> {
+    pub fn new<R: 'static + Component>(component: R, node: Rc<HtmlNode<'doc>>) -> Binding<'doc> {
+        let props = lookup_props(&node, component.props());
         Binding {
             component: Box::new(component),
             node: node,
+            props: props,
+            fingerprint: None,
+            snapshot: None,
             handlers: vec![],
+            syncs: vec![],
+            lifecycle: dispatch_lifecycle::<R>,
+            last_render: None,
+            refs: HashMap::new(),
+            children: vec![],
+            named_events: vec![],
+            scope: R::styles().map(|_| css::scope_attr::<R>()),
         }
     }
 
     pub fn add_handler(&mut self,
                        event_type: EventType,
                        el: Option<String>,
-                       event_handler: Rc<Fn(webplatform::Event<'doc>, usize) + 'doc>,
+                       event_handler: HandlerFn<'doc>,
                        registered_nodes: Vec<Rc<HtmlNode<'doc>>>) {
         let handler = Handler {
             el: el,
@@ -43,22 +99,80 @@ impl<'doc> Binding<'doc> {
         self.handlers.push(handler);
     }
 
+    pub fn add_sync(&mut self, sync: SyncFn<'doc>) {
+        self.syncs.push(sync);
+    }
+
+    /// Write component data back into the rendered DOM
+    pub fn sync(&mut self) {
+        let syncs = self.syncs.clone();
+        for sync in syncs {
+            sync(self);
+        }
+    }
+
+
+    /// The output of the last render, if any
+    pub fn last_render(&self) -> Option<&str> {
+        self.last_render.as_ref().map(|output| &**output)
+    }
+
+    /// Record that named handlers listen for an event type, returning false if they already were
+    pub fn add_named_event(&mut self, event: &'static str) -> bool {
+        if self.named_events.contains(&event) {
+            return false;
+        }
+        self.named_events.push(event);
+        true
+    }
+
+    /// Record a view bound within this binding's node, to be rerendered along with it
+    pub fn add_child(&mut self, child_id: TypedKey) {
+        self.children.push(child_id);
+    }
+
+    /// The element rendered with `ref="<name>"`, as of the last render
+    pub fn node_ref(&self, name: &str) -> Option<Rc<HtmlNode<'doc>>> {
+        self.refs.get(name).cloned()
+    }
 
-    pub fn component<R>(&self) -> &R
+    // Collect the elements with a `ref` attribute, skipping those rendered by child views
+    fn collect_refs(&mut self) {
+        let root = &self.node;
+        let in_child_view = |node: &HtmlNode<'doc>| {
+            let placeholder = |ancestor: &HtmlNode<'doc>| {
+                if ancestor.attr_get_str(html::CHILD_ATTR).is_empty() { None } else { Some(()) }
+            };
+            node.parent().and_then(|parent| dom::find_within(&parent, root, placeholder)).is_some()
+        };
+        self.refs = self.node
+            .element_query_all(&format!("[{}]", html::REF_ATTR))
+            .into_iter()
+            .filter(|node| !in_child_view(node))
+            .map(|node| (node.attr_get_str(html::REF_ATTR), Rc::new(node)))
+            .collect();
+    }
+
+    pub fn component<R>(&self) -> Result<&R>
         where R: Renderable
     {
-        self.component.downcast_ref().unwrap()
+        self.component.downcast_ref().ok_or_else(component_type_mismatch)
     }
 
-    pub fn component_mut<R>(&mut self) -> &mut R
+    pub fn component_mut<R>(&mut self) -> Result<&mut R>
         where R: Renderable
     {
-        self.component.downcast_mut().unwrap()
+        self.component.downcast_mut().ok_or_else(component_type_mismatch)
     }
 }
 
+// Data for a single data_id, borrowed independently of other partitions
+//
+// Readers share the value's `Rc`, while a `DataMutRef` takes the value out until it is dropped.
+type Partition = Rc<RefCell<Option<Rc<Box<Any>>>>>;
+
 // Map data_id to data
-type DataStore = HashMap<TypedKey, Box<Any>>;
+type DataStore = HashMap<TypedKey, Partition>;
 
 // Map view_id to binding
 type BindingStore<'doc> = HashMap<TypedKey, Rc<RefCell<Binding<'doc>>>>;
@@ -69,6 +183,18 @@ type ObserverStore = HashMap<TypedKey, HashSet<TypedKey>>;
 // Set of view_id that need rerendered
 type RenderQueue = Vec<TypedKey>;
 
+// Set of view_id whose observed app data changed since they last rendered
+type ChangedSet = HashSet<TypedKey>;
+
+// Optional callback receiving structured trace records
+type Tracer = Option<Rc<Fn(&TraceRecord)>>;
+
+// Map view_id to the timers and other work to cancel when the view is unbound
+type TaskStore<'doc> = HashMap<TypedKey, Vec<TaskHandle<'doc>>>;
+
+// Map data_id to the latest fetch loading into it
+type FetchStore<'doc> = HashMap<TypedKey, TaskHandle<'doc>>;
+
 
 pub struct AppState<'doc> {
     pub document: Rc<Document<'doc>>,
@@ -76,86 +202,271 @@ pub struct AppState<'doc> {
     state: Rc<RefCell<DataStore>>,
     observers: Rc<RefCell<ObserverStore>>,
     render_queue: Rc<RefCell<RenderQueue>>,
+    data_changed: Rc<RefCell<ChangedSet>>,
+    tracer: Rc<RefCell<Tracer>>,
+    tasks: Rc<RefCell<TaskStore<'doc>>>,
+    fetches: Rc<RefCell<FetchStore<'doc>>>,
+    transport: Rc<RefCell<Rc<Transport<'doc> + 'doc>>>,
+    socket_transport: Rc<RefCell<Rc<SocketTransport<'doc> + 'doc>>>,
+    timers: Rc<RefCell<Rc<Timers<'doc> + 'doc>>>,
+    executor: Rc<Executor<'doc>>,
+    styled: Rc<RefCell<HashSet<TypeId>>>,
 }
 
-impl<'doc> AppState<'doc> {
-    pub fn new(document: Document<'doc>) -> AppState<'doc> {
+impl AppState<'static> {
+    pub fn new(document: Document<'static>) -> AppState<'static> {
         AppState {
             bindings: Rc::new(RefCell::new(HashMap::new())),
             state: Rc::new(RefCell::new(HashMap::new())),
             observers: Rc::new(RefCell::new(HashMap::new())),
             render_queue: Rc::new(RefCell::new(Vec::new())),
+            data_changed: Rc::new(RefCell::new(HashSet::new())),
+            tracer: Rc::new(RefCell::new(None)),
+            tasks: Rc::new(RefCell::new(HashMap::new())),
+            fetches: Rc::new(RefCell::new(HashMap::new())),
+            transport: Rc::new(RefCell::new(Rc::new(XhrTransport))),
+            socket_transport: Rc::new(RefCell::new(Rc::new(BrowserSocketTransport))),
+            timers: Rc::new(RefCell::new(Rc::new(BrowserTimers))),
+            executor: Rc::new(Executor::new(executor::schedule_wake)),
+            styled: Rc::new(RefCell::new(HashSet::new())),
             document: Rc::new(document),
         }
     }
+}
+
+impl<'doc> AppState<'doc> {
+    pub fn set_timers(&self, timers: Rc<Timers<'doc> + 'doc>) {
+        *self.timers.borrow_mut() = timers;
+    }
+
+    pub fn timers(&self) -> Rc<Timers<'doc> + 'doc> {
+        self.timers.borrow().clone()
+    }
+
+    pub fn set_tracer(&self, tracer: Tracer) {
+        *self.tracer.borrow_mut() = tracer;
+    }
+
+    pub fn set_transport(&self, transport: Rc<Transport<'doc> + 'doc>) {
+        *self.transport.borrow_mut() = transport;
+    }
+
+    pub fn transport(&self) -> Rc<Transport<'doc> + 'doc> {
+        self.transport.borrow().clone()
+    }
+
+    pub fn set_socket_transport(&self, transport: Rc<SocketTransport<'doc> + 'doc>) {
+        *self.socket_transport.borrow_mut() = transport;
+    }
+
+    pub fn socket_transport(&self) -> Rc<SocketTransport<'doc> + 'doc> {
+        self.socket_transport.borrow().clone()
+    }
+
+    /// Record the fetch loading into an app data partition, cancelling any previous one
+    pub fn replace_fetch(&self, data_id: TypedKey, task: TaskHandle<'doc>) {
+        let previous = self.fetches.borrow_mut().insert(data_id, task);
+        if let Some(previous) = previous {
+            previous.cancel();
+        }
+    }
+
+    pub fn executor(&self) -> Rc<Executor<'doc>> {
+        self.executor.clone()
+    }
+
+    /// Record that the stylesheet of a component type was injected, returning false if it already was
+    pub fn add_styled(&self, type_id: TypeId) -> bool {
+        self.styled.borrow_mut().insert(type_id)
+    }
+
+    pub fn create_element(&self, tag: &str) -> Result<Node<'doc>> {
+        let node = self.document.element_create(tag).ok_or_else(|| Error::InvalidElement(tag.to_owned()))?;
+        Ok(Node::new(Rc::new(self.clone()), Rc::new(node)))
+    }
+
+    /// Log a trace record and pass it to the tracer, if one is set
+    pub fn trace(&self, record: TraceRecord) {
+        qdebug!(target: record.target(), "{}", record);
+
+        // Clone the tracer out so that it may replace itself
+        let tracer = self.tracer.borrow().clone();
+        if let Some(tracer) = tracer {
+            tracer(&record);
+        }
+    }
 
     pub fn data<T: 'static>(&self, key: &str) -> Option<DataRef<T>> {
-        let data_id = TypedKey::new::<T>(key);
-        let borrowed_state = self.state.borrow();
-        if !borrowed_state.contains_key(&data_id) {
-            return None;
+        match self.try_data(key) {
+            Ok(data) => Some(data),
+            Err(Error::MissingData(_)) => None,
+            Err(err) => panic!("{}", err),
         }
-        let owned_ref = Ref::map(borrowed_state, |state| {
-            let entry = state.get(&data_id).unwrap();
-            entry.downcast_ref().unwrap()
-        });
-        Some(DataRef {
-            reference: &*owned_ref,
-            _owner: owned_ref,
+    }
+
+    pub fn try_data<T: 'static>(&self, key: &str) -> Result<DataRef<T>> {
+        let partition = self.partition::<T>(key)?;
+        let value = partition.borrow().clone().ok_or_else(|| data_borrow_conflict(key))?;
+        Ok(DataRef {
+            value: value,
+            phantom: PhantomData,
         })
     }
 
     pub fn data_mut<T: 'static>(&self, key: &str) -> Option<DataMutRef<T>> {
+        match self.try_data_mut(key) {
+            Ok(data) => Some(data),
+            Err(Error::MissingData(_)) => None,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    pub fn try_data_mut<T: 'static>(&self, key: &str) -> Result<DataMutRef<T>> {
+        let data = self.take_data::<T>(key)?;
+
         // Look up observers, and enqueue them for re-render
+        self.enqueue_observers(&TypedKey::new::<T>(key));
+        Ok(data)
+    }
+
+    // Take the value out of its partition until the returned reference is dropped,
+    //   which fails while any other reference to it is alive
+    fn take_data<T: 'static>(&self, key: &str) -> Result<DataMutRef<T>> {
+        let partition = self.partition::<T>(key)?;
+        let value = take_unshared(&partition).ok_or_else(|| data_borrow_conflict(key))?;
+        Ok(DataMutRef {
+            value: Some(value),
+            partition: partition,
+            phantom: PhantomData,
+        })
+    }
+
+    pub fn data_set<T: 'static>(&self, key: &str, data: T) {
+        self.try_data_set(key, data).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_data_set<T: 'static>(&self, key: &str, data: T) -> Result<()> {
         let data_id = TypedKey::new::<T>(key);
-        let borrowed_state = self.state.borrow_mut();
-        if !borrowed_state.contains_key(&data_id) {
-            return None;
-        }
 
-        {
-            let observers = self.observers.borrow();
-            if let Some(partition_observers) = observers.get(&data_id) {
-                let mut queue = self.render_queue.borrow_mut();
-                for observer in partition_observers {
-                    queue.push(observer.clone());
-                }
+        // Replace the value in place so that the partition stays shared with any outstanding borrows
+        match self.partition::<T>(key) {
+            Ok(partition) => {
+                take_unshared(&partition).ok_or_else(|| data_borrow_conflict(key))?;
+                let entry: Box<Any> = Box::new(data);
+                *partition.borrow_mut() = Some(Rc::new(entry));
+            }
+            Err(_) => {
+                let entry: Box<Any> = Box::new(data);
+                let mut borrowed_state = self.state.borrow_mut();
+                borrowed_state.insert(data_id.clone(), Rc::new(RefCell::new(Some(Rc::new(entry)))));
             }
         }
+        self.enqueue_observers(&data_id);
+        Ok(())
+    }
+
+    pub fn data_remove<T: 'static>(&self, key: &str) -> Option<T> {
+        self.try_data_remove(key).unwrap_or_else(|err| panic!("{}", err))
+    }
 
+    /// Like `data_remove`, but returns an error instead of panicking if the partition is borrowed
+    pub fn try_data_remove<T: 'static>(&self, key: &str) -> Result<Option<T>> {
+        let data_id = TypedKey::new::<T>(key);
+        let partition = match self.partition::<T>(key) {
+            Ok(partition) => partition,
+            Err(_) => return Ok(None),
+        };
+        let entry = take_unshared(&partition).ok_or_else(|| data_borrow_conflict(key))?;
 
-        let mut owned_ref = RefMut::map(borrowed_state, |mut state| {
-            let mut entry = state.get_mut(&data_id).expect("Failed to get mutable state");
-            entry.downcast_mut::<T>().unwrap()
-        });
-        Some(DataMutRef {
-            reference: &mut *owned_ref,
-            _owner: owned_ref,
-        })
+        self.state.borrow_mut().remove(&data_id);
+        self.enqueue_observers(&data_id);
+        Ok(Some(*entry.downcast::<T>().expect("app data partition held a mismatched type")))
     }
 
-    pub fn data_set<T: 'static>(&self, key: &str, data: T) {
+    pub fn data_entry<'a, T: 'static>(&'a self, key: &str) -> DataEntry<'a, 'doc, T> {
+        DataEntry {
+            app: self,
+            key: key.to_owned(),
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn data_update<T, F, U>(&self, key: &str, f: F) -> Option<U>
+        where T: 'static,
+              F: FnOnce(&mut T) -> U
+    {
+        self.data_mut::<T>(key).map(|mut data| f(&mut *data))
+    }
+
+    pub fn try_data_update<T, F, U>(&self, key: &str, f: F) -> Result<U>
+        where T: 'static,
+              F: FnOnce(&mut T) -> U
+    {
+        self.try_data_mut::<T>(key).map(|mut data| f(&mut *data))
+    }
+
+    pub fn data_update_if_changed<T, F, U>(&self, key: &str, f: F) -> Option<U>
+        where T: 'static + Clone + PartialEq,
+              F: FnOnce(&mut T) -> U
+    {
+        match self.try_data_update_if_changed(key, f) {
+            Ok(result) => Some(result),
+            Err(Error::MissingData(_)) => None,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    pub fn try_data_update_if_changed<T, F, U>(&self, key: &str, f: F) -> Result<U>
+        where T: 'static + Clone + PartialEq,
+              F: FnOnce(&mut T) -> U
+    {
+        let (result, changed) = {
+            let mut data = self.take_data::<T>(key)?;
+            let original = data.clone();
+            let result = f(&mut *data);
+            (result, *data != original)
+        };
+
+        if changed {
+            self.enqueue_observers(&TypedKey::new::<T>(key));
+        }
+        Ok(result)
+    }
+
+    fn data_exists<T: 'static>(&self, key: &str) -> bool {
         let data_id = TypedKey::new::<T>(key);
+        self.state.borrow().contains_key(&data_id)
+    }
 
-        {
-            let observers = self.observers.borrow();
-            if let Some(partition_observers) = observers.get(&data_id) {
-                let mut queue = self.render_queue.borrow_mut();
-                for observer in partition_observers {
-                    queue.push(observer.clone());
-                }
+    // Only holds the store borrow long enough to clone the partition handle,
+    //   so that different partitions can be borrowed independently
+    fn partition<T: 'static>(&self, key: &str) -> Result<Partition> {
+        let data_id = TypedKey::new::<T>(key);
+        self.state.borrow().get(&data_id).cloned().ok_or_else(|| Error::MissingData(key.to_owned()))
+    }
+
+    fn enqueue_observers(&self, data_id: &TypedKey) {
+        let observers = self.observers.borrow();
+        if let Some(partition_observers) = observers.get(data_id) {
+            let mut queue = self.render_queue.borrow_mut();
+            let mut changed = self.data_changed.borrow_mut();
+            for observer in partition_observers {
+                queue.push(observer.clone());
+                changed.insert(observer.clone());
             }
         }
+    }
 
-        let mut borrowed_state = self.state.borrow_mut();
-        borrowed_state.insert(data_id, Box::new(data));
+    /// Whether app data observed by a view changed since the view last rendered
+    pub fn observed_data_changed(&self, view_id: &TypedKey) -> bool {
+        self.data_changed.borrow().contains(view_id)
     }
 
-    pub fn insert_binding<R: 'static + Renderable>(&self,
-                                                   key: &str,
-                                                   component: R,
-                                                   node: Rc<HtmlNode<'doc>>)
-                                                   -> Rc<RefCell<Binding<'doc>>> {
+    pub fn insert_binding<R: 'static + Component>(&self,
+                                                  key: &str,
+                                                  component: R,
+                                                  node: Rc<HtmlNode<'doc>>)
+                                                  -> Rc<RefCell<Binding<'doc>>> {
         let binding = Binding::new(component, node);
         let rc_binding = Rc::new(RefCell::new(binding));
         {
@@ -166,10 +477,59 @@ impl<'doc> AppState<'doc> {
         rc_binding
     }
 
-    pub fn remove_binding<R: 'static + Renderable>(&self, key: &str) -> Option<Rc<RefCell<Binding<'doc>>>> {
-        let mut bindings = self.bindings.borrow_mut();
-        let view_id = TypedKey::new::<R>(key);
-        bindings.remove(&view_id)
+    /// Remove a binding, after running its `on_unmount` hook
+    ///
+    /// The view will no longer observe app data or be rerendered, and its timers
+    ///   and other tasks are cancelled.
+    pub fn remove_binding(&self, view_id: &TypedKey) -> Option<Rc<RefCell<Binding<'doc>>>> {
+        let binding = match self.bindings.borrow().get(view_id) {
+            Some(binding) => binding.clone(),
+            None => return None,
+        };
+        let lifecycle = binding.borrow().lifecycle;
+        lifecycle(&Rc::new(self.clone()), view_id, &binding, Lifecycle::Unmount);
+        self.discard_binding(view_id)
+    }
+
+    /// Remove a binding without dispatching `Lifecycle::Unmount`, e.g. when it never rendered
+    pub fn discard_binding(&self, view_id: &TypedKey) -> Option<Rc<RefCell<Binding<'doc>>>> {
+        let tasks = self.tasks.borrow_mut().remove(view_id);
+        for task in tasks.unwrap_or_else(Vec::new) {
+            task.cancel();
+        }
+
+        for partition_observers in self.observers.borrow_mut().values_mut() {
+            partition_observers.remove(view_id);
+        }
+        self.render_queue.borrow_mut().retain(|queued| queued != view_id);
+        self.data_changed.borrow_mut().remove(view_id);
+        self.bindings.borrow_mut().remove(view_id)
+    }
+
+    /// Track work to cancel when the view is unbound
+    pub fn add_task(&self, view_id: &TypedKey, task: TaskHandle<'doc>) {
+        let mut tasks = self.tasks.borrow_mut();
+        let view_tasks = tasks.entry(view_id.clone()).or_insert_with(Vec::new);
+        view_tasks.retain(|task| !task.is_done());
+        view_tasks.push(task);
+    }
+
+    /// Poll any woken futures, then process the render queue, logging rather than returning any failure
+    pub fn flush_render_queue(&self) {
+        self.executor.run();
+        if let Err(err) = self.process_render_queue() {
+            warn!(target: "quasar::render", "Error processing render queue: {}", err);
+        }
+    }
+
+    /// The node a view is bound to, or `None` if it is unbound or its binding is mutably borrowed
+    pub fn binding_node(&self, view_id: &TypedKey) -> Option<Rc<HtmlNode<'doc>>> {
+        let binding = self.bindings.borrow().get(view_id).cloned();
+        binding.and_then(|binding| binding.try_borrow().ok().map(|binding| binding.node.clone()))
+    }
+
+    pub fn is_bound(&self, view_id: &TypedKey) -> bool {
+        self.bindings.borrow().contains_key(view_id)
     }
 
     pub fn enqueue_render(&self, view_id: TypedKey) {
@@ -178,32 +538,94 @@ impl<'doc> AppState<'doc> {
     }
 
     pub fn add_observer(&self, data_id: TypedKey, view_id: TypedKey) {
-        let mut observers = self.observers.borrow_mut();
-        let mut partition = observers.entry(data_id).or_insert_with(|| HashSet::new());
-        partition.insert(view_id);
+        let added = {
+            let mut observers = self.observers.borrow_mut();
+            let mut partition = observers.entry(data_id.clone()).or_insert_with(|| HashSet::new());
+            partition.insert(view_id.clone())
+        };
+        if added {
+            self.trace(TraceRecord::ObserverAdded { data: data_id.key(), view: view_id.key() });
+        }
     }
 
-    pub fn process_render_queue(&self) {
-        let mut queue = self.render_queue.borrow_mut();
-        println!("Processing render queue (len={})", queue.len());
-        let bindings = self.bindings.borrow();
-        for view_id in queue.iter() {
-            let binding = match bindings.get(&view_id) {
-                Some(b) => b,
-                None => {
-                    // TODO: removed dropped event handlers
-                    return println!("failed to get binding for view (probably zombie handler)");
-                }
+    /// Rerender every queued view
+    ///
+    /// A failure to render one view does not prevent rendering the rest of the queue;
+    ///   the first error encountered is returned once the queue is drained.
+    /// Renders enqueued by lifecycle hooks while processing are left for the next time
+    ///   the queue is processed.
+    pub fn process_render_queue(&self) -> Result<()> {
+        let queue = mem::replace(&mut *self.render_queue.borrow_mut(), Vec::new());
+        self.trace(TraceRecord::RenderQueueProcessed { len: queue.len() });
+
+        let mut rendered = HashSet::new();
+        let mut result = Ok(());
+        for view_id in queue {
+            if !rendered.insert(view_id.clone()) {
+                continue;
+            }
+
+            let binding = self.bindings.borrow().get(&view_id).cloned();
+            let render_result = match binding {
+                Some(binding) => self.render_binding(&view_id, &binding),
+                None => Err(Error::MissingBinding(view_id.key().to_owned())),
             };
+            if let Err(err) = render_result {
+                warn!(target: "quasar::render", "Failed to rerender view: {}", err);
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+        result
+    }
+
+    /// Render a view even if its output is unchanged, e.g. after a parent render replaced its content
+    pub fn force_render(&self, view_id: &TypedKey) -> Result<()> {
+        let binding = self.bindings.borrow().get(view_id).cloned()
+            .ok_or_else(|| Error::MissingBinding(view_id.key().to_owned()))?;
+        binding.borrow_mut().last_render = None;
+        self.render_binding(view_id, &binding)
+    }
+
+    /// Render a binding into its node, running the component's render lifecycle hooks
+    pub fn render_binding(&self, view_id: &TypedKey, binding: &Rc<RefCell<Binding<'doc>>>) -> Result<()> {
+        let app = Rc::new(self.clone());
+        let (lifecycle, first_render) = {
             let binding = binding.borrow();
-            let ref component = binding.component;
+            (binding.lifecycle, binding.last_render.is_none())
+        };
+
+        if !first_render && !lifecycle(&app, view_id, binding, Lifecycle::ShouldRender) {
+            self.trace(TraceRecord::RenderSkipped { view: view_id.key() });
+            return Ok(());
+        }
+        self.data_changed.borrow_mut().remove(view_id);
+        lifecycle(&app, view_id, binding, Lifecycle::BeforeRender);
 
-            // Rerender the main binding
-            println!("Rerender node {:?}", &binding.node);
+        // Rerender the main binding
+        let output = {
+            let binding = binding.borrow();
+            let render_node = Node::new(app.clone(), binding.node.clone());
+            let app_context = AppContext::new(app.clone(), Some(view_id.clone()));
+            let output = binding.component.render(&render_node, &app_context)?;
+            match binding.scope {
+                Some(ref attr) => css::scope_markup(&output, attr),
+                None => output,
+            }
+        };
 
-            let render_node = Node::new(Rc::new(self.clone()), binding.node.clone());
-            let app_context = AppContext::new(Rc::new(self.clone()), Some(view_id.clone()));
-            binding.node.html_patch(&component.render(&render_node, &app_context));
+        // Identical output would only churn the DOM (and lose its state, like input focus)
+        let mut patched = false;
+        if binding.borrow().last_render.as_ref() == Some(&output) {
+            self.trace(TraceRecord::RenderSkipped { view: view_id.key() });
+        } else {
+            patched = true;
+            let mut binding = binding.borrow_mut();
+            binding.node.html_patch(&output);
+            binding.last_render = Some(output);
+            binding.collect_refs();
+            self.trace(TraceRecord::ViewRendered { view: view_id.key() });
 
             // Attach any event handlers.
             // Since we patched the DOM, we need to reattach any event handlers
@@ -220,14 +642,40 @@ impl<'doc> AppState<'doc> {
                             continue;
                         }
                         let f = handler.event_handler.clone();
-                        rc_node.on(handler.event_type.name(), move |event| f(event, i));
+                        let matched = (*node).clone();
+                        rc_node.on(handler.event_type.name(), move |event| f(event, i, matched.clone()));
                     }
-                    println!("On handlers REregistered for nodes: {:?}", &rc_nodes);
+                    self.trace(TraceRecord::HandlerRegistered {
+                        event: handler.event_type.name(),
+                        selector: Some(el),
+                        count: rc_nodes.len(),
+                    });
                     *registered_nodes = rc_nodes;
                 }
             }
         }
-        queue.clear();
+
+        // Patching replaced the content of child views with their empty placeholders
+        if patched {
+            self.render_children(binding);
+        }
+
+        binding.borrow_mut().sync();
+        lifecycle(&app, view_id, binding, Lifecycle::AfterRender);
+        Ok(())
+    }
+
+    fn render_children(&self, binding: &Rc<RefCell<Binding<'doc>>>) {
+        let children = {
+            let mut binding = binding.borrow_mut();
+            binding.children.retain(|child_id| self.is_bound(child_id));
+            binding.children.clone()
+        };
+        for child_id in &children {
+            if let Err(err) = self.force_render(child_id) {
+                warn!(target: "quasar::render", "Failed to rerender child view: {}", err);
+            }
+        }
     }
 
     fn clone(&self) -> AppState<'doc> {
@@ -236,11 +684,50 @@ impl<'doc> AppState<'doc> {
             state: self.state.clone(),
             observers: self.observers.clone(),
             render_queue: self.render_queue.clone(),
+            data_changed: self.data_changed.clone(),
+            tracer: self.tracer.clone(),
+            tasks: self.tasks.clone(),
+            fetches: self.fetches.clone(),
+            transport: self.transport.clone(),
+            socket_transport: self.socket_transport.clone(),
+            timers: self.timers.clone(),
+            executor: self.executor.clone(),
+            styled: self.styled.clone(),
             document: self.document.clone(),
         }
     }
 }
 
+fn component_type_mismatch() -> Error {
+    Error::TypeMismatch("component bound to view".to_owned())
+}
+
+fn data_borrow_conflict(key: &str) -> Error {
+    Error::BorrowConflict(format!("App data '{}'", key))
+}
+
+// Take the value out of `partition` if no reference to it is alive
+fn take_unshared(partition: &Partition) -> Option<Box<Any>> {
+    let mut slot = partition.borrow_mut();
+    match slot.take().map(Rc::try_unwrap) {
+        Some(Ok(value)) => Some(value),
+        Some(Err(shared)) => {
+            *slot = Some(shared);
+            None
+        }
+        None => None,
+    }
+}
+
+// Partitions are keyed by `TypeId`, so the downcast only fails on a bug in the store
+fn downcast_partition<T: 'static>(entry: &Box<Any>) -> &T {
+    entry.downcast_ref().expect("app data partition held a mismatched type")
+}
+
+fn downcast_partition_mut<T: 'static>(entry: &mut Box<Any>) -> &mut T {
+    entry.downcast_mut().expect("app data partition held a mismatched type")
+}
+
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub struct TypedKey {
     tid: TypeId,
@@ -254,36 +741,146 @@ impl TypedKey {
             key: key.to_owned(),
         }
     }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+/// A single app data partition which may or may not be set
+pub struct DataEntry<'a, 'doc: 'a, T> {
+    app: &'a AppState<'doc>,
+    key: String,
+    phantom: PhantomData<T>,
+}
+
+impl<'a, 'doc, T: 'static> DataEntry<'a, 'doc, T> {
+    /// Set the partition to `default` if it is absent, and return a mutable reference to it
+    pub fn or_insert(self, default: T) -> DataMutRef<'a, T> {
+        self.or_insert_with(|| default)
+    }
+
+    /// Set the partition to the result of `f` if it is absent, and return a mutable reference to it
+    ///
+    /// Only inserting enqueues the observers of the partition for rerendering, so changes made
+    ///   through the returned reference don't; use `data_update` for those.
+    pub fn or_insert_with<F: FnOnce() -> T>(self, f: F) -> DataMutRef<'a, T> {
+        if !self.app.data_exists::<T>(&self.key) {
+            self.app.data_set(&self.key, f());
+        }
+        self.app.take_data(&self.key).unwrap_or_else(|err| panic!("{}", err))
+    }
 }
 
 /// Reference to generic app data
-pub struct DataRef<'a, T: 'a> {
-    _owner: Ref<'a, T>,
-    reference: *const T,
+///
+/// Only the borrowed partition is locked; other app data may be borrowed while this is held.
+pub struct DataRef<'a, T: 'static> {
+    value: Rc<Box<Any>>,
+    phantom: PhantomData<&'a T>,
 }
 
 /// Mutable reference to generic app data
-pub struct DataMutRef<'a, T: 'a> {
-    _owner: RefMut<'a, T>,
-    reference: *mut T,
+///
+/// Only the borrowed partition is locked; other app data may be borrowed while this is held.
+pub struct DataMutRef<'a, T: 'static> {
+    // Taken out of `partition` until this is dropped
+    value: Option<Box<Any>>,
+    partition: Partition,
+    phantom: PhantomData<&'a mut T>,
 }
 
-impl<'a, T> Deref for DataRef<'a, T> {
+impl<'a, T: 'static> Deref for DataRef<'a, T> {
     type Target = T;
     fn deref(&self) -> &Self::Target {
-        unsafe { &*self.reference }
+        downcast_partition(&self.value)
     }
 }
 
-impl<'a, T> Deref for DataMutRef<'a, T> {
+impl<'a, T: 'static> Deref for DataMutRef<'a, T> {
     type Target = T;
     fn deref(&self) -> &Self::Target {
-        unsafe { &*self.reference }
+        downcast_partition(self.value.as_ref().expect("app data taken twice"))
     }
 }
 
-impl<'a, T> DerefMut for DataMutRef<'a, T> {
+impl<'a, T: 'static> DerefMut for DataMutRef<'a, T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        unsafe { &mut *self.reference }
+        downcast_partition_mut(self.value.as_mut().expect("app data taken twice"))
+    }
+}
+
+impl<'a, T: 'static> Drop for DataMutRef<'a, T> {
+    fn drop(&mut self) {
+        if let Some(value) = self.value.take() {
+            *self.partition.borrow_mut() = Some(Rc::new(value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webplatform;
+
+    #[test]
+    fn data_mut_fails_while_data_is_borrowed() {
+        let app = AppState::new(webplatform::init());
+        app.data_set("count", 1u32);
+
+        let first = app.try_data::<u32>("count").unwrap();
+        let second = app.try_data::<u32>("count").unwrap();
+        assert_eq!((*first, *second), (1, 1));
+        match app.try_data_mut::<u32>("count") {
+            Err(Error::BorrowConflict(_)) => (),
+            _ => panic!("expected a borrow conflict"),
+        }
+
+        drop(first);
+        drop(second);
+        *app.try_data_mut::<u32>("count").unwrap() += 1;
+        assert_eq!(*app.try_data::<u32>("count").unwrap(), 2);
+    }
+
+    #[test]
+    fn data_fails_while_data_is_mutably_borrowed() {
+        let app = AppState::new(webplatform::init());
+        app.data_set("count", 1u32);
+
+        let data = app.try_data_mut::<u32>("count").unwrap();
+        assert!(app.try_data::<u32>("count").is_err());
+        assert!(app.try_data_mut::<u32>("count").is_err());
+        assert!(app.try_data_set("count", 5u32).is_err());
+        assert!(app.try_data_remove::<u32>("count").is_err());
+        drop(data);
+        assert_eq!(app.try_data_remove::<u32>("count").unwrap(), Some(1));
+    }
+
+    #[test]
+    fn different_partitions_borrow_independently() {
+        let app = AppState::new(webplatform::init());
+        app.data_set("a", 1u32);
+        app.data_set("b", 2u32);
+        app.data_set("a", String::from("text"));
+
+        let mut a = app.try_data_mut::<u32>("a").unwrap();
+        let mut b = app.try_data_mut::<u32>("b").unwrap();
+        let text = app.try_data::<String>("a").unwrap();
+        *a += *b;
+        *b = 0;
+        assert_eq!((*a, *b, &**text), (3, 0, "text"));
+    }
+
+    #[test]
+    fn data_entry_only_enqueues_observers_on_insert() {
+        let app = AppState::new(webplatform::init());
+        app.add_observer(TypedKey::new::<u32>("count"), TypedKey::new::<String>("view"));
+
+        assert_eq!(*app.data_entry::<u32>("count").or_insert(1), 1);
+        assert_eq!(app.render_queue.borrow().len(), 1);
+
+        app.render_queue.borrow_mut().clear();
+        assert_eq!(*app.data_entry::<u32>("count").or_insert_with(|| 2), 1);
+        assert!(app.render_queue.borrow().is_empty());
     }
 }
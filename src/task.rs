@@ -0,0 +1,58 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+/// Handle to scheduled or in-flight work (e.g. a timer)
+///
+/// Work started from the `AppContext` of a view is cancelled automatically when the view is unbound.
+#[derive(Clone)]
+pub struct TaskHandle<'doc> {
+    state: Rc<TaskState<'doc>>,
+}
+
+struct TaskState<'doc> {
+    cancelled: Cell<bool>,
+    finished: Cell<bool>,
+    on_cancel: RefCell<Option<Box<Fn() + 'doc>>>,
+}
+
+impl<'doc> TaskHandle<'doc> {
+    pub(crate) fn new() -> TaskHandle<'doc> {
+        TaskHandle {
+            state: Rc::new(TaskState {
+                cancelled: Cell::new(false),
+                finished: Cell::new(false),
+                on_cancel: RefCell::new(None),
+            }),
+        }
+    }
+
+    // Mark the work completed, so that cancelling it is a no-op
+    pub(crate) fn finish(&self) {
+        self.state.finished.set(true);
+        self.state.on_cancel.borrow_mut().take();
+    }
+
+    // Set the cleanup to run if the work is cancelled (e.g. clearing a browser timer)
+    pub(crate) fn on_cancel<F: Fn() + 'doc>(&self, f: F) {
+        *self.state.on_cancel.borrow_mut() = Some(Box::new(f));
+    }
+
+    /// Cancel the work, so that its callbacks are never called (again)
+    pub fn cancel(&self) {
+        if self.state.cancelled.replace(true) || self.state.finished.get() {
+            return;
+        }
+        if let Some(on_cancel) = self.state.on_cancel.borrow_mut().take() {
+            on_cancel();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.get()
+    }
+
+    /// Whether the work was cancelled or has completed
+    pub fn is_done(&self) -> bool {
+        self.state.cancelled.get() || self.state.finished.get()
+    }
+}
@@ -0,0 +1,398 @@
+//! Quasar's built-in template engine
+//!
+//! Templates render any `Encodable` data, HTML-escaping interpolated values by default:
+//!
+//! ```text
+//! <h1>{{title}}</h1>
+//! {{#if !items}}<p>Nothing to do</p>{{/if}}
+//! <ul>
+//!   {{#for item in items}}
+//!     <li data-index="{{@index}}">{{> item item}}</li>
+//!   {{/for}}
+//! </ul>
+//! {{{trusted_html}}}
+//! ```
+//!
+//! `{{> name path}}` renders the partial registered as `name`, scoped to the value at `path`,
+//!   inlining its markup into the component's own output.
+//!
+//! `{{< name}}` is a component tag: it renders the placeholder element of `html::child(name)`,
+//!   into which a child component is bound, typically from `Component::onload`:
+//!
+//! ```ignore
+//! fn onload(view: &View<Self>) {
+//!     view.bind(&html::child_selector("counter"), CounterData::default());
+//! }
+//! ```
+//!
+//! Child components bound within a view are rerendered into their placeholders whenever
+//!   the view's output changes.
+//!
+//! At compile time, `#[derive(Template)]` (enabled by the `derive` feature) implements `Renderable`
+//!   for a `RustcEncodable` struct using a `#[template = "path/relative/to/crate.html"]`
+//!   or `#[template_string = "..."]` attribute, and `#[template_partials(item = "src/item.html")]`
+//!   registers its partials. The derive parses the templates as it expands, so malformed templates
+//!   are compile errors, and rendering only binds the data. Since the parsed template is cached
+//!   per type, the derived impl requires generic type parameters to be `'static`.
+//!
+//! Runtime-loaded templates are compiled with `Template::compile` and rendered from `Renderable`:
+//!
+//! ```ignore
+//! impl Renderable for Hello {
+//!     fn render(&self, node: &Node, _app: &AppContext) -> Result<String> {
+//!         self.template.render_with_props(&self.data, &node.get_properties(&["name"]))
+//!     }
+//! }
+//! ```
+
+use std::any::TypeId;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use rustc_serialize::Encodable;
+use rustc_serialize::json::{self, Json};
+
+use quasar_template::{self as parser, Path};
+#[doc(hidden)]
+pub use quasar_template::Part;
+use html::{self, escape};
+use {Error, Properties, Result};
+
+/// A compiled template along with the partials it may render
+#[derive(Debug)]
+pub struct Template {
+    parts: Vec<Part>,
+    partials: HashMap<String, Rc<Template>>,
+}
+
+// A level of lookup: the root data, a partial's context, or a loop variable
+struct Scope<'a> {
+    var: Option<&'a str>,
+    value: &'a Json,
+    index: Option<usize>,
+    key: Option<&'a str>,
+}
+
+impl Template {
+    /// Compile template source, returning `Error::Template` if it is malformed
+    pub fn compile(src: &str) -> Result<Template> {
+        let parts = parser::parse(src).map_err(Error::Template)?;
+        Ok(Template::from_parts(parts))
+    }
+
+    /// A template from parts already parsed, e.g. by `#[derive(Template)]`
+    #[doc(hidden)]
+    pub fn from_parts(parts: Vec<Part>) -> Template {
+        Template {
+            parts: parts,
+            partials: HashMap::new(),
+        }
+    }
+
+    /// Register a partial rendered by `{{> name}}` tags
+    ///
+    /// Partials may themselves render any partial registered on this template, e.g. recursively
+    ///   to render nested data, but rendering fails with `Error::Render` if partials nest
+    ///   more than 64 deep, as when partials render each other unconditionally.
+    pub fn partial(mut self, name: &str, partial: Template) -> Template {
+        self.partials.insert(name.to_owned(), Rc::new(partial));
+        self
+    }
+
+    /// Render `data` encoded as JSON
+    pub fn render<T: Encodable>(&self, data: &T) -> Result<String> {
+        self.render_json(&encode(data)?)
+    }
+
+    /// Render `data` with component properties available as `props.*`
+    pub fn render_with_props<T: Encodable>(&self, data: &T, props: &Properties) -> Result<String> {
+        let mut data = encode(data)?;
+        match data {
+            Json::Object(ref mut map) => {
+                let props = props.iter()
+                    .map(|(k, v)| (k.to_string(), Json::String(v.clone())))
+                    .collect();
+                map.insert("props".to_owned(), Json::Object(props));
+            }
+            _ => return Err(Error::Render("Template data with props must encode as an object".to_owned())),
+        }
+        self.render_json(&data)
+    }
+
+    pub fn render_json(&self, data: &Json) -> Result<String> {
+        let mut out = String::new();
+        let mut scopes = vec![Scope { var: None, value: data, index: None, key: None }];
+        render_parts(&self.parts, &self.partials, &mut scopes, 0, &mut out)?;
+        Ok(out)
+    }
+}
+
+/// Build the template for `T` once per thread, reusing it for subsequent renders
+///
+/// Used by `#[derive(Template)]`, which has already parsed the template into the parts `build` assembles.
+#[doc(hidden)]
+pub fn cached<T, F>(build: F) -> Rc<Template>
+    where T: 'static, F: FnOnce() -> Template
+{
+    thread_local!(static TEMPLATES: RefCell<HashMap<TypeId, Rc<Template>>> = RefCell::new(HashMap::new()));
+
+    let type_id = TypeId::of::<T>();
+    if let Some(template) = TEMPLATES.with(|t| t.borrow().get(&type_id).cloned()) {
+        return template;
+    }
+    let template = Rc::new(build());
+    TEMPLATES.with(|t| t.borrow_mut().insert(type_id, template.clone()));
+    template
+}
+
+fn encode<T: Encodable>(data: &T) -> Result<Json> {
+    let encoded = json::encode(data)
+        .map_err(|err| Error::Render(format!("Failed to encode template data: {}", err)))?;
+    Json::from_str(&encoded).map_err(|err| Error::Render(format!("Failed to encode template data: {}", err)))
+}
+
+// How deeply partials may render partials, which stops partials that render each other in a cycle
+const MAX_PARTIAL_DEPTH: usize = 64;
+
+fn render_parts<'a>(parts: &'a [Part],
+                    partials: &'a HashMap<String, Rc<Template>>,
+                    scopes: &mut Vec<Scope<'a>>,
+                    depth: usize,
+                    out: &mut String)
+                    -> Result<()> {
+    for part in parts {
+        match *part {
+            Part::Text(ref text) => out.push_str(text),
+            Part::Value { ref path, escape: should_escape } => {
+                if let Some(value) = lookup(scopes, path) {
+                    let text = to_text(&value);
+                    if should_escape {
+                        out.push_str(&escape(&text));
+                    } else {
+                        out.push_str(&text);
+                    }
+                }
+            }
+            Part::If { ref path, negate, ref then, ref otherwise } => {
+                let truthy = lookup(scopes, path).map_or(false, |value| is_truthy(&value));
+                let branch = if truthy != negate { then } else { otherwise };
+                render_parts(branch, partials, scopes, depth, out)?;
+            }
+            Part::For { ref var, ref path, ref body, ref empty } => {
+                let items: Vec<(Option<&'a str>, &'a Json)> = match lookup_ref(scopes, path) {
+                    Some(&Json::Array(ref items)) => items.iter().map(|item| (None, item)).collect(),
+                    Some(&Json::Object(ref map)) => map.iter().map(|(k, v)| (Some(&**k), v)).collect(),
+                    _ => vec![],
+                };
+                if items.is_empty() {
+                    render_parts(empty, partials, scopes, depth, out)?;
+                }
+                for (index, (key, item)) in items.into_iter().enumerate() {
+                    scopes.push(Scope { var: Some(&**var), value: item, index: Some(index), key: key });
+                    let result = render_parts(body, partials, scopes, depth, out);
+                    scopes.pop();
+                    result?;
+                }
+            }
+            Part::Partial { ref name, ref path } => {
+                let partial = partials.get(name)
+                    .ok_or_else(|| Error::Render(format!("No partial named '{}'", name)))?;
+                if depth == MAX_PARTIAL_DEPTH {
+                    let msg = format!("Partial '{}' nested more than {} deep; do partials render each other in a cycle?",
+                                      name, MAX_PARTIAL_DEPTH);
+                    return Err(Error::Render(msg));
+                }
+                let context = match *path {
+                    Some(ref path) => Some(lookup_ref(scopes, path).unwrap_or(&Json::Null)),
+                    None => None,
+                };
+                if let Some(value) = context {
+                    scopes.push(Scope { var: None, value: value, index: None, key: None });
+                }
+                let result = render_parts(&partial.parts, partials, scopes, depth + 1, out);
+                if context.is_some() {
+                    scopes.pop();
+                }
+                result?;
+            }
+            Part::Child { ref name } => out.push_str(html::child(name).as_str()),
+        }
+    }
+    Ok(())
+}
+
+// Resolve a path, including the `@index` and `@key` of the innermost loop
+fn lookup<'a>(scopes: &[Scope<'a>], path: &Path) -> Option<Cow<'a, Json>> {
+    match path.first().map(|s| &**s) {
+        Some("@index") => scopes.iter().rev().filter_map(|s| s.index).next().map(|i| Cow::Owned(Json::U64(i as u64))),
+        Some("@key") => scopes.iter().rev().filter_map(|s| s.key).next().map(|k| Cow::Owned(Json::String(k.to_owned()))),
+        _ => lookup_ref(scopes, path).map(Cow::Borrowed),
+    }
+}
+
+// Resolve a path against the innermost loop variable it names,
+//   otherwise against the innermost unnamed scope (the root data or a partial's context)
+fn lookup_ref<'a>(scopes: &[Scope<'a>], path: &Path) -> Option<&'a Json> {
+    let (mut value, rest) = match path.first() {
+        None => (scopes.last().map(|s| s.value)?, &path[..]),
+        Some(first) => {
+            match scopes.iter().rev().find(|s| s.var == Some(&**first)) {
+                Some(scope) => (scope.value, &path[1..]),
+                None => (scopes.iter().rev().find(|s| s.var.is_none())?.value, &path[..]),
+            }
+        }
+    };
+    for segment in rest {
+        value = match *value {
+            Json::Object(ref map) => map.get(segment)?,
+            Json::Array(ref items) => items.get(segment.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+fn is_truthy(value: &Json) -> bool {
+    match *value {
+        Json::Null => false,
+        Json::Boolean(b) => b,
+        Json::I64(n) => n != 0,
+        Json::U64(n) => n != 0,
+        Json::F64(n) => n != 0.0,
+        Json::String(ref s) => !s.is_empty(),
+        Json::Array(ref items) => !items.is_empty(),
+        Json::Object(ref map) => !map.is_empty(),
+    }
+}
+
+fn to_text(value: &Json) -> String {
+    match *value {
+        Json::Null => String::new(),
+        Json::String(ref s) => s.clone(),
+        Json::I64(n) => n.to_string(),
+        Json::U64(n) => n.to_string(),
+        Json::F64(n) => n.to_string(),
+        Json::Boolean(b) => b.to_string(),
+        ref other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustc_serialize::json::Json;
+
+    fn render(src: &str, data: &str) -> Result<String> {
+        Template::compile(src)?.render_json(&Json::from_str(data).unwrap())
+    }
+
+    #[test]
+    fn escapes_interpolated_values() {
+        let data = r#"{"name": "<b>\"Tom\" & 'Jerry'</b>"}"#;
+        assert_eq!(render("<p>{{name}}</p>", data).unwrap(),
+                   "<p>&lt;b&gt;&quot;Tom&quot; &amp; &#39;Jerry&#39;&lt;/b&gt;</p>");
+        assert_eq!(render("<p>{{{name}}}</p>", data).unwrap(), "<p><b>\"Tom\" & 'Jerry'</b></p>");
+    }
+
+    #[test]
+    fn interpolates_paths_and_scalars() {
+        let data = r#"{"user": {"name": "Ann", "age": 7}, "missing": null, "ok": true}"#;
+        assert_eq!(render("{{ user.name }} {{user.age}} [{{missing}}] {{ok}} [{{nope.deeper}}]", data).unwrap(),
+                   "Ann 7 [] true []");
+    }
+
+    #[test]
+    fn renders_conditionals() {
+        let src = "{{#if items}}some{{else}}none{{/if}}|{{#if !done}}todo{{/if}}";
+        assert_eq!(render(src, r#"{"items": [1], "done": false}"#).unwrap(), "some|todo");
+        assert_eq!(render(src, r#"{"items": [], "done": true}"#).unwrap(), "none|");
+        assert_eq!(render(src, r#"{"items": "", "done": 1}"#).unwrap(), "none|");
+    }
+
+    #[test]
+    fn renders_loops() {
+        let src = "{{#for item in items}}<li data-index=\"{{@index}}\">{{item.label}}</li>{{else}}empty{{/for}}";
+        assert_eq!(render(src, r#"{"items": [{"label": "a"}, {"label": "<b>"}]}"#).unwrap(),
+                   "<li data-index=\"0\">a</li><li data-index=\"1\">&lt;b&gt;</li>");
+        assert_eq!(render(src, r#"{"items": []}"#).unwrap(), "empty");
+        assert_eq!(render("{{#for v in map}}{{@key}}={{v}};{{/for}}", r#"{"map": {"a": 1, "b": 2}}"#).unwrap(),
+                   "a=1;b=2;");
+    }
+
+    #[test]
+    fn loop_variables_shadow_root_fields() {
+        let src = "{{#for name in names}}{{name}}/{{title}} {{/for}}";
+        assert_eq!(render(src, r#"{"names": ["x", "y"], "title": "t", "name": "root"}"#).unwrap(), "x/t y/t ");
+    }
+
+    #[test]
+    fn renders_partials() {
+        let template = Template::compile("<ul>{{#for item in items}}{{> item item}}{{/for}}</ul>{{> footer}}")
+            .unwrap()
+            .partial("item", Template::compile("<li>{{label}}</li>").unwrap())
+            .partial("footer", Template::compile("<p>{{count}}</p>").unwrap());
+        let data = Json::from_str(r#"{"items": [{"label": "a"}, {"label": "b"}], "count": 2}"#).unwrap();
+        assert_eq!(template.render_json(&data).unwrap(), "<ul><li>a</li><li>b</li></ul><p>2</p>");
+    }
+
+    #[test]
+    fn missing_partial_is_a_render_error() {
+        match render("{{> nope}}", "{}") {
+            Err(Error::Render(_)) => (),
+            other => panic!("expected a render error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn renders_recursive_partials_over_nested_data() {
+        let template = Template::compile("{{> tree}}")
+            .unwrap()
+            .partial("tree", Template::compile("{{name}}({{#for c in children}}{{> tree c}}{{/for}})").unwrap());
+        let data = Json::from_str(r#"{"name": "a", "children": [{"name": "b", "children": []}, {"name": "c"}]}"#).unwrap();
+        assert_eq!(template.render_json(&data).unwrap(), "a(b()c())");
+    }
+
+    #[test]
+    fn partial_cycles_are_render_errors() {
+        let template = Template::compile("{{> ping}}")
+            .unwrap()
+            .partial("ping", Template::compile("ping {{> pong}}").unwrap())
+            .partial("pong", Template::compile("pong {{> ping}}").unwrap());
+        match template.render_json(&Json::Null) {
+            Err(Error::Render(ref msg)) if msg.contains("cycle") => (),
+            other => panic!("expected a render error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn renders_child_placeholders() {
+        assert_eq!(render("<div>{{< counter}}</div>", "{}").unwrap(),
+                   format!("<div>{}</div>", html::child("counter")));
+    }
+
+    #[test]
+    fn renders_props() {
+        let mut props = Properties::new();
+        props.insert("name", "<Ann>".to_owned());
+        let template = Template::compile("{{props.name}}").unwrap();
+        assert_eq!(template.render_with_props(&Json::from_str("{}").unwrap(), &props).unwrap(), "&lt;Ann&gt;");
+    }
+
+    #[test]
+    fn skips_comments() {
+        assert_eq!(render("a{{! ignored }}b", "{}").unwrap(), "ab");
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        for src in &["{{#if a}}", "{{/if}}", "{{#if a}}{{/for}}", "{{else}}", "{{#if a}}{{else}}{{else}}{{/if}}",
+                     "{{#for x of y}}{{/for}}", "{{#while a}}{{/while}}", "{{name", "{{{name}}", "{{a..b}}",
+                     "{{> a b c}}", "{{< a b}}"] {
+            match Template::compile(src) {
+                Err(Error::Template(_)) => (),
+                other => panic!("expected a template error for {:?}, got {:?}", src, other),
+            }
+        }
+    }
+}
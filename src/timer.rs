@@ -0,0 +1,291 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use platform;
+use state::{AppState, TypedKey};
+use task::TaskHandle;
+
+use AppContext;
+
+/// Schedules the callbacks of `set_timeout` and `set_interval`
+///
+/// Defaults to the browser's timers, and can be replaced with `QuasarApp::set_timers`,
+///   e.g. by `ManualTimers` in tests.
+pub trait Timers<'doc> {
+    /// Call `f` once after `ms` milliseconds, returning an id for `clear`
+    fn set_timeout(&self, ms: u32, f: Box<FnMut() + 'doc>) -> i32;
+
+    /// Call `f` every `ms` milliseconds, returning an id for `clear`
+    fn set_interval(&self, ms: u32, f: Box<FnMut() + 'doc>) -> i32;
+
+    /// Cancel a timeout or interval, which is a no-op once a timeout has fired
+    fn clear(&self, id: i32);
+}
+
+/// The browser's `setTimeout` and `setInterval`
+///
+/// The browser calls back from its event loop, so only `'static` callbacks can be scheduled.
+pub struct BrowserTimers;
+
+impl Timers<'static> for BrowserTimers {
+    fn set_timeout(&self, ms: u32, mut f: Box<FnMut()>) -> i32 {
+        platform::set_timeout(ms, move || f())
+    }
+
+    fn set_interval(&self, ms: u32, mut f: Box<FnMut()>) -> i32 {
+        platform::set_interval(ms, move || f())
+    }
+
+    fn clear(&self, id: i32) {
+        platform::clear_timer(id)
+    }
+}
+
+struct ManualTimer<'doc> {
+    id: i32,
+    due: u64,
+    interval: Option<u32>,
+    // Taken out while an interval is firing
+    callback: Option<Box<FnMut() + 'doc>>,
+}
+
+/// Timers driven by a clock that only moves when advanced, for testing without an event loop
+pub struct ManualTimers<'doc> {
+    now: Cell<u64>,
+    next_id: Cell<i32>,
+    timers: RefCell<Vec<ManualTimer<'doc>>>,
+}
+
+impl<'doc> Default for ManualTimers<'doc> {
+    fn default() -> ManualTimers<'doc> {
+        ManualTimers::new()
+    }
+}
+
+impl<'doc> ManualTimers<'doc> {
+    pub fn new() -> ManualTimers<'doc> {
+        ManualTimers {
+            now: Cell::new(0),
+            next_id: Cell::new(1),
+            timers: RefCell::new(vec![]),
+        }
+    }
+
+    /// Move the clock forward by `ms`, firing the timers due by then in order
+    pub fn advance(&self, ms: u32) {
+        let until = self.now.get() + ms as u64;
+        loop {
+            let next = self.timers
+                .borrow()
+                .iter()
+                .filter(|timer| timer.due <= until && timer.callback.is_some())
+                .min_by_key(|timer| (timer.due, timer.id))
+                .map(|timer| timer.id);
+            let id = match next {
+                Some(id) => id,
+                None => break,
+            };
+
+            // Take the callback out while calling it, since it may add or clear timers
+            let (mut callback, interval) = {
+                let mut timers = self.timers.borrow_mut();
+                let pos = timers.iter().position(|timer| timer.id == id).expect("due timer was removed");
+                self.now.set(timers[pos].due);
+                match timers[pos].interval {
+                    Some(interval) => (timers[pos].callback.take(), Some(interval)),
+                    None => (timers.remove(pos).callback, None),
+                }
+            };
+            if let Some(ref mut callback) = callback {
+                callback();
+            }
+
+            // Reschedule an interval, unless its callback cleared it
+            if let Some(interval) = interval {
+                let mut timers = self.timers.borrow_mut();
+                if let Some(timer) = timers.iter_mut().find(|timer| timer.id == id) {
+                    timer.due += interval.max(1) as u64;
+                    timer.callback = callback;
+                }
+            }
+        }
+        self.now.set(until);
+    }
+
+    /// The number of timeouts and intervals that have not fired or been cleared
+    pub fn pending(&self) -> usize {
+        self.timers.borrow().len()
+    }
+
+    fn add(&self, ms: u32, interval: Option<u32>, f: Box<FnMut() + 'doc>) -> i32 {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        self.timers.borrow_mut().push(ManualTimer {
+            id: id,
+            due: self.now.get() + ms as u64,
+            interval: interval,
+            callback: Some(f),
+        });
+        id
+    }
+}
+
+impl<'doc> Timers<'doc> for ManualTimers<'doc> {
+    fn set_timeout(&self, ms: u32, f: Box<FnMut() + 'doc>) -> i32 {
+        self.add(ms, None, f)
+    }
+
+    fn set_interval(&self, ms: u32, f: Box<FnMut() + 'doc>) -> i32 {
+        self.add(ms.max(1), Some(ms), f)
+    }
+
+    fn clear(&self, id: i32) {
+        self.timers.borrow_mut().retain(|timer| timer.id != id);
+    }
+}
+
+/// Call `f` once after `ms` milliseconds, then process the render queue
+///
+/// When started for a view, the timeout is cancelled if the view is unbound first.
+pub fn set_timeout<'doc, F>(app: &Rc<AppState<'doc>>, view_id: Option<TypedKey>, ms: u32, f: F) -> TaskHandle<'doc>
+    where F: FnOnce(AppContext<'doc>) + 'doc
+{
+    let task = TaskHandle::new();
+    let callback = {
+        let app = app.clone();
+        let task = task.clone();
+        let view_id = view_id.clone();
+        let mut f = Some(f);
+        move || {
+            if task.is_done() {
+                return;
+            }
+            task.finish();
+            if let Some(f) = f.take() {
+                f(AppContext::new(app.clone(), view_id.clone()));
+                app.flush_render_queue();
+            }
+        }
+    };
+
+    let timers = app.timers();
+    let id = timers.set_timeout(ms, Box::new(callback));
+    task.on_cancel(move || timers.clear(id));
+    if let Some(ref view_id) = view_id {
+        app.add_task(view_id, task.clone());
+    }
+    task
+}
+
+/// Call `f` every `ms` milliseconds until cancelled, processing the render queue after each call
+///
+/// When started for a view, the interval is cancelled when the view is unbound.
+pub fn set_interval<'doc, F>(app: &Rc<AppState<'doc>>, view_id: Option<TypedKey>, ms: u32, f: F) -> TaskHandle<'doc>
+    where F: FnMut(AppContext<'doc>) + 'doc
+{
+    let task = TaskHandle::new();
+    let callback = {
+        let app = app.clone();
+        let task = task.clone();
+        let view_id = view_id.clone();
+        let mut f = f;
+        move || {
+            if task.is_done() {
+                return;
+            }
+            f(AppContext::new(app.clone(), view_id.clone()));
+            app.flush_render_queue();
+        }
+    };
+
+    let timers = app.timers();
+    let id = timers.set_interval(ms, Box::new(callback));
+    task.on_cancel(move || timers.clear(id));
+    if let Some(ref view_id) = view_id {
+        app.add_task(view_id, task.clone());
+    }
+    task
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webplatform;
+
+    fn app() -> (Rc<AppState<'static>>, Rc<ManualTimers<'static>>) {
+        let app = Rc::new(AppState::new(webplatform::init()));
+        let timers = Rc::new(ManualTimers::new());
+        app.set_timers(timers.clone());
+        (app, timers)
+    }
+
+    #[test]
+    fn timeouts_fire_once_when_due() {
+        let (app, timers) = app();
+        let fired = Rc::new(Cell::new(0));
+        let task = {
+            let fired = fired.clone();
+            set_timeout(&app, None, 100, move |_| fired.set(fired.get() + 1))
+        };
+
+        timers.advance(99);
+        assert_eq!(fired.get(), 0);
+        timers.advance(1);
+        timers.advance(1000);
+        assert_eq!(fired.get(), 1);
+        assert!(task.is_done() && !task.is_cancelled());
+        assert_eq!(timers.pending(), 0);
+    }
+
+    #[test]
+    fn cancelled_timeouts_never_fire() {
+        let (app, timers) = app();
+        let fired = Rc::new(Cell::new(false));
+        let task = {
+            let fired = fired.clone();
+            set_timeout(&app, None, 100, move |_| fired.set(true))
+        };
+
+        task.cancel();
+        timers.advance(1000);
+        assert!(!fired.get());
+        assert!(task.is_cancelled());
+        assert_eq!(timers.pending(), 0);
+    }
+
+    #[test]
+    fn intervals_fire_until_cancelled() {
+        let (app, timers) = app();
+        let fired = Rc::new(Cell::new(0));
+        let task = {
+            let fired = fired.clone();
+            set_interval(&app, None, 100, move |_| fired.set(fired.get() + 1))
+        };
+
+        timers.advance(350);
+        assert_eq!(fired.get(), 3);
+        task.cancel();
+        timers.advance(1000);
+        assert_eq!(fired.get(), 3);
+        assert_eq!(timers.pending(), 0);
+    }
+
+    #[test]
+    fn unbinding_a_view_cancels_its_timers() {
+        let (app, timers) = app();
+        let view_id = TypedKey::new::<u32>("view");
+        let fired = Rc::new(Cell::new(0));
+        let (timeout, interval) = {
+            let (a, b) = (fired.clone(), fired.clone());
+            (set_timeout(&app, Some(view_id.clone()), 100, move |_| a.set(a.get() + 1)),
+             set_interval(&app, Some(view_id.clone()), 50, move |_| b.set(b.get() + 1)))
+        };
+
+        timers.advance(50);
+        assert_eq!(fired.get(), 1);
+        app.discard_binding(&view_id);
+        timers.advance(1000);
+        assert_eq!(fired.get(), 1);
+        assert!(timeout.is_cancelled() && interval.is_cancelled());
+        assert_eq!(timers.pending(), 0);
+    }
+}
@@ -0,0 +1,65 @@
+use std::fmt;
+
+// Debug diagnostics are compiled out of release builds entirely,
+//   while warnings and errors are always routed to the logger
+macro_rules! qdebug {
+    (target: $target:expr, $($arg:tt)+) => {
+        if cfg!(debug_assertions) {
+            debug!(target: $target, $($arg)+);
+        }
+    };
+}
+
+/// Structured record of quasar's internal activity, passed to the tracer set with `QuasarApp::set_tracer`
+#[derive(Debug)]
+pub enum TraceRecord<'a> {
+    /// An event handler was invoked, optionally within the view with the given key
+    EventFired { event: &'static str, view: Option<&'a str> },
+    /// An event handler was attached to `count` nodes, optionally matching `selector`
+    HandlerRegistered { event: &'static str, selector: Option<&'a str>, count: usize },
+    /// The render queue is about to rerender `len` views
+    RenderQueueProcessed { len: usize },
+    /// A view was rendered into the DOM
+    ViewRendered { view: &'a str },
+    /// A queued rerender was skipped because the view was unchanged
+    RenderSkipped { view: &'a str },
+    /// A view started observing an app data partition
+    ObserverAdded { data: &'a str, view: &'a str },
+}
+
+impl<'a> TraceRecord<'a> {
+    /// The log target this record is emitted under
+    pub fn target(&self) -> &'static str {
+        match *self {
+            TraceRecord::EventFired { .. } |
+            TraceRecord::HandlerRegistered { .. } => "quasar::events",
+            TraceRecord::RenderQueueProcessed { .. } |
+            TraceRecord::ViewRendered { .. } |
+            TraceRecord::RenderSkipped { .. } => "quasar::render",
+            TraceRecord::ObserverAdded { .. } => "quasar::data",
+        }
+    }
+}
+
+impl<'a> fmt::Display for TraceRecord<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TraceRecord::EventFired { event, view: Some(view) } => {
+                write!(f, "Event '{}' fired on view {}", event, view)
+            }
+            TraceRecord::EventFired { event, view: None } => write!(f, "Event '{}' fired", event),
+            TraceRecord::HandlerRegistered { event, selector: Some(selector), count } => {
+                write!(f, "'{}' handler registered for {} nodes matching '{}'", event, count, selector)
+            }
+            TraceRecord::HandlerRegistered { event, selector: None, count } => {
+                write!(f, "'{}' handler registered for {} nodes", event, count)
+            }
+            TraceRecord::RenderQueueProcessed { len } => write!(f, "Processing render queue (len={})", len),
+            TraceRecord::ViewRendered { view } => write!(f, "Rendered view {}", view),
+            TraceRecord::RenderSkipped { view } => write!(f, "Skipped rendering unchanged view {}", view),
+            TraceRecord::ObserverAdded { data, view } => {
+                write!(f, "View {} observing app data '{}'", view, data)
+            }
+        }
+    }
+}
@@ -1,11 +1,22 @@
-use state::{AppState, Binding, TypedKey};
+use state::{AppState, Binding, Lifecycle, TypedKey};
 use std::cell::{RefCell, Ref, RefMut};
 use std::rc::Rc;
 use std::marker::PhantomData;
+use std::str::FromStr;
 use webplatform::{self, HtmlNode};
 use uuid::Uuid;
+use rustc_serialize::Decodable;
+use form::Form;
+use platform;
+use events::{EVENT_TYPES, item_key};
+use task::TaskHandle;
+use timer;
+use executor;
+use css;
+use futures::Future;
 
-use {Queryable, Component, HasBind, Renderable, Event, EventType, AppContext, Node};
+use {Queryable, Component, HasBind, Renderable, Event, EventType, AppContext, Node, Error, Result, TraceRecord,
+     lookup_props};
 
 
 pub struct View<'doc, R: 'static + Renderable> {
@@ -39,7 +50,13 @@ impl<'doc, R: 'static + Renderable> View<'doc, R> {
         let binding = self.binding.clone();
         let node = self.node.clone();
 
-        let event_handler = Rc::new(move |evt: webplatform::Event<'doc>, i| {
+        let event_name = event.name();
+
+        let event_handler = Rc::new(move |evt: webplatform::Event<'doc>, i, matched: Rc<HtmlNode<'doc>>| {
+            // Handlers stay attached to the DOM after unbinding, but should no longer fire
+            if !app.is_bound(&TypedKey::new::<R>(&key)) {
+                return;
+            }
             let node: View<'doc, R> = View {
                 app: app.clone(),
                 key: key.clone(),
@@ -47,40 +64,67 @@ impl<'doc, R: 'static + Renderable> View<'doc, R> {
                 binding: binding.clone(),
                 phantom: PhantomData,
             };
-            println!("Event fired on {:?} for target {:?}",
-                     &node.binding.borrow().node,
-                     evt.target);
+            app.trace(TraceRecord::EventFired { event: event_name, view: Some(&key) });
             let target_node = evt.target.expect("Event did not have a target");
             let event = Event {
                 app: AppContext::new(app.clone(), Some(TypedKey::new::<R>(&key))),
+                key: item_key(&matched, &node.node),
+                matched: Node::new(app.clone(), matched),
                 binding: node,
                 target: Node::new(app.clone(), Rc::new(target_node)),
-                // FIXME: strange to attach a meaningless index here
                 index: i,
             };
             f(event);
-            app.process_render_queue();
+            app.flush_render_queue();
         });
 
         // Attach event_handler to the DOM
         let f = event_handler.clone();
-
-        self.node.on(event.name(), move |evt| f(evt, 0));
+        let matched = self.node.clone();
+        self.node.on(event.name(), move |evt| f(evt, 0, matched.clone()));
 
         // Attach event_handler to binding for future rendering
         self.binding.borrow_mut().add_handler(event.clone(), None, event_handler, vec![self.node.clone()]);
-        println!("On handler registered for {:?}", self.node);
+        self.app.trace(TraceRecord::HandlerRegistered { event: event.name(), selector: None, count: 1 });
     }
 
     pub fn on_each<F>(&self, event: EventType, el: &str, f: F)
         where F: Fn(Event<Self>) + 'doc
+    {
+        self.on_each_with(event, el, false, f)
+    }
+
+    /// Handle submission of the forms matching `el`
+    ///
+    /// The browser's default submission is prevented, and the named controls within the form
+    ///   are decoded into `T` and checked by the validators of `form`. Any errors are published
+    ///   where `form` directs, and `f` is only called with the decoded value when there are none.
+    pub fn on_submit<T, F>(&self, el: &str, form: Form<R, T>, f: F)
+        where T: 'static + Decodable,
+              F: Fn(Event<Self>, T) + 'doc
+    {
+        self.on_each_with(EventType::Submit, el, true, move |mut evt| {
+            if let Some(value) = form.submit(&mut evt) {
+                f(evt, value);
+            }
+        })
+    }
+
+    fn on_each_with<F>(&self, event: EventType, el: &str, prevent_default: bool, f: F)
+        where F: Fn(Event<Self>) + 'doc
     {
         let app = self.app.clone();
         let key = self.key.clone();
         let binding = self.binding.clone();
         let node = self.node.clone();
 
-        let event_handler = Rc::new(move |evt: webplatform::Event<'doc>, i| {
+        let event_name = event.name();
+
+        let event_handler = Rc::new(move |evt: webplatform::Event<'doc>, i, matched: Rc<HtmlNode<'doc>>| {
+            // Handlers stay attached to the DOM after unbinding, but should no longer fire
+            if !app.is_bound(&TypedKey::new::<R>(&key)) {
+                return;
+            }
             let node: View<'doc, R> = View {
                 app: app.clone(),
                 key: key.clone(),
@@ -88,35 +132,226 @@ impl<'doc, R: 'static + Renderable> View<'doc, R> {
                 binding: binding.clone(),
                 phantom: PhantomData,
             };
-            println!("Event fired on {:?} for target {:?}",
-                     &node.binding.borrow().node,
-                     evt.target);
+            app.trace(TraceRecord::EventFired { event: event_name, view: Some(&key) });
             let target_node = evt.target.expect("Event did not have a target");
             let event = Event {
                 app: AppContext::new(app.clone(), Some(TypedKey::new::<R>(&key))),
+                key: item_key(&matched, &node.node),
+                matched: Node::new(app.clone(), matched),
                 binding: node,
                 target: Node::new(app.clone(), Rc::new(target_node)),
                 index: i,
             };
             f(event);
-            app.process_render_queue();
+            app.flush_render_queue();
         });
 
         // Attach event_handler to the DOM
-        let nodes = self.node.element_query_all(&el);
+        let nodes: Vec<_> = self.node.element_query_all(&el).into_iter().map(Rc::new).collect();
         for (i, node) in nodes.iter().enumerate() {
             let f = event_handler.clone();
-            node.on(event.name(), move |evt| f(evt, i));
+            let matched = node.clone();
+            node.on(event.name(), move |evt| f(evt, i, matched.clone()));
         }
-        println!("On handlers registered for nodes: {:?}", &nodes);
+        self.app.trace(TraceRecord::HandlerRegistered { event: event.name(), selector: Some(el), count: nodes.len() });
 
         // Attach event_handler to binding for future rendering
-        self.binding.borrow_mut().add_handler(event.clone(), Some(el.to_owned()), event_handler, nodes.into_iter().map(Rc::new).collect());
+        self.binding.borrow_mut().add_handler(event.clone(), Some(el.to_owned()), event_handler, nodes);
+
+        // Covers the matching elements of later renders too
+        if prevent_default {
+            platform::prevent_default(&self.node, event.name(), el);
+        }
     }
 }
 
 
 impl<'doc, R: 'static + Renderable> View<'doc, R> {
+    /// Two-way bind the value of the controls matching `el` to a component field
+    ///
+    /// Input and change events parse the control's value into the field (ignoring values
+    ///   that fail to parse), and every render writes the field back into the control.
+    ///
+    /// ```ignore
+    /// view.bind_value("#name-field", |d| &mut d.name);
+    /// ```
+    pub fn bind_value<T, F>(&self, el: &str, field: F)
+        where T: 'static + FromStr + ToString + PartialEq,
+              F: Fn(&mut R) -> &mut T + 'doc
+    {
+        self.bind_control(el,
+                          &[EventType::Input, EventType::Change],
+                          field,
+                          |node| node.value().parse().ok(),
+                          |node, value| {
+                              let value = value.to_string();
+                              if node.prop_get_str("value") != value {
+                                  node.prop_set_str("value", &value);
+                              }
+                          });
+    }
+
+    /// Two-way bind the checked state of the checkboxes matching `el` to a component field
+    ///
+    /// Radio buttons are checked or unchecked as a group, so bind them with `bind_radio`.
+    pub fn bind_checked<F>(&self, el: &str, field: F)
+        where F: Fn(&mut R) -> &mut bool + 'doc
+    {
+        self.bind_control(el,
+                          &[EventType::Change],
+                          field,
+                          |node| Some(node.checked()),
+                          |node, checked| node.prop_set_i32("checked", *checked as i32));
+    }
+
+    /// Two-way bind a group of radio buttons matching `el` to a component field
+    ///
+    /// Checking a radio button parses its `value` into the field, and every render checks
+    ///   the radio button whose `value` matches the field (unchecking the others).
+    ///
+    /// ```ignore
+    /// view.bind_radio("input[name=size]", |d| &mut d.size);
+    /// ```
+    pub fn bind_radio<T, F>(&self, el: &str, field: F)
+        where T: 'static + FromStr + ToString + PartialEq,
+              F: Fn(&mut R) -> &mut T + 'doc
+    {
+        self.bind_control(el,
+                          &[EventType::Change],
+                          field,
+                          |node| if node.checked() { node.value().parse().ok() } else { None },
+                          |node, value| {
+                              let checked = node.prop_get_str("value") == value.to_string();
+                              node.prop_set_i32("checked", checked as i32);
+                          });
+    }
+
+    fn bind_control<T, F, G, S>(&self, el: &str, events: &[EventType], field: F, get: G, set: S)
+        where T: 'static + PartialEq,
+              F: Fn(&mut R) -> &mut T + 'doc,
+              G: for<'a> Fn(&Node<'a>) -> Option<T> + 'doc,
+              S: Fn(&HtmlNode<'doc>, &T) + 'doc
+    {
+        let field = Rc::new(field);
+        let get = Rc::new(get);
+        for event in events {
+            let field = field.clone();
+            let get = get.clone();
+            self.on_each(event.clone(), el, move |evt| {
+                if let Some(value) = get(&evt.target) {
+                    evt.binding.update_data(|data| {
+                        let current = field(data);
+                        if *current == value {
+                            return false;
+                        }
+                        *current = value;
+                        true
+                    });
+                }
+            });
+        }
+
+        let el = el.to_owned();
+        let sync = Rc::new(move |binding: &mut Binding<'doc>| {
+            let nodes = binding.node.element_query_all(&el);
+            if let Ok(data) = binding.component_mut::<R>() {
+                let value = field(data);
+                for node in &nodes {
+                    set(node, value);
+                }
+            }
+        });
+
+        // Bring the already rendered controls in line with the field
+        let mut binding = self.binding.borrow_mut();
+        binding.add_sync(sync);
+        binding.sync();
+    }
+
+    // Mutate the component data, only enqueueing a rerender if `f` reports a change
+    fn update_data<F: FnOnce(&mut R) -> bool>(&self, f: F) {
+        let changed = match self.binding.borrow_mut().component_mut::<R>() {
+            Ok(data) => f(data),
+            Err(_) => false,
+        };
+        if changed {
+            self.app.enqueue_render(TypedKey::new::<R>(&self.key));
+        }
+    }
+
+    /// Unbind the component from this view's node
+    ///
+    /// This calls `Component::on_unmount`, clears the rendered content, and stops the view
+    ///   from observing app data, being rerendered, or dispatching its event handlers.
+    pub fn unbind(&self) {
+        let view_id = TypedKey::new::<R>(&self.key);
+        if self.app.remove_binding(&view_id).is_some() {
+            self.node.html_set("");
+        }
+    }
+
+    /// Call `f` with this view once after `ms` milliseconds (see `AppContext::set_timeout`)
+    ///
+    /// The timeout is cancelled if the view is unbound first.
+    pub fn set_timeout<F>(&self, ms: u32, f: F) -> TaskHandle<'doc>
+        where F: FnOnce(View<'doc, R>, AppContext<'doc>) + 'doc
+    {
+        let view = self.handle();
+        timer::set_timeout(&self.app, Some(self.view_id()), ms, move |app| f(view, app))
+    }
+
+    /// Call `f` with this view every `ms` milliseconds until cancelled or the view is unbound
+    pub fn set_interval<F>(&self, ms: u32, f: F) -> TaskHandle<'doc>
+        where F: FnMut(View<'doc, R>, AppContext<'doc>) + 'doc
+    {
+        let view = self.handle();
+        let mut f = f;
+        timer::set_interval(&self.app, Some(self.view_id()), ms, move |app| f(view.handle(), app))
+    }
+
+    /// Run `future` on the event loop, calling `done` with this view and its result when it completes
+    ///
+    /// The future is dropped without calling `done` if the view is unbound first.
+    pub fn spawn<F, C>(&self, future: F, done: C) -> TaskHandle<'doc>
+        where F: Future + 'doc,
+              C: FnOnce(View<'doc, R>, AppContext<'doc>, ::std::result::Result<F::Item, F::Error>) + 'doc
+    {
+        let view = self.handle();
+        executor::spawn(&self.app, Some(self.view_id()), future, move |app, result| done(view, app, result))
+    }
+
+    /// The element this view rendered with a `ref="<name>"` attribute
+    ///
+    /// Refs are collected after each render, excluding elements rendered by child views
+    ///   in `html::child` placeholders, so the node always belongs to this view instance.
+    /// Returns `Error::MissingRef` if the last render had no such element,
+    ///   or `Error::BorrowConflict` if the component data is mutably borrowed.
+    pub fn r#ref(&self, name: &str) -> Result<Node<'doc>> {
+        let binding = self.binding.try_borrow().map_err(|_| self.borrow_conflict())?;
+        let node = binding.node_ref(name).ok_or_else(|| Error::MissingRef(name.to_owned()))?;
+        Ok(Node::new(self.app.clone(), node))
+    }
+
+    // Another handle to this view, e.g. for passing to callbacks
+    fn handle(&self) -> View<'doc, R> {
+        View {
+            app: self.app.clone(),
+            node: self.node.clone(),
+            key: self.key.clone(),
+            binding: self.binding.clone(),
+            phantom: PhantomData,
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn view_id(&self) -> TypedKey {
+        TypedKey::new::<R>(&self.key)
+    }
+
+    fn borrow_conflict(&self) -> Error {
+        Error::BorrowConflict(format!("Component data for view '{}'", self.key))
+    }
+
     pub fn set(&self, prop: &str, value: &str) {
         self.node.prop_set_str(prop, value);
     }
@@ -128,6 +363,50 @@ impl<'doc, R: 'static + Renderable> View<'doc, R> {
     pub fn checked(&self) -> bool {
         self.node.prop_get_i32("checked") != 0
     }
+
+    /// The view's element as a `Node`, e.g. for `view.node().add_class("active")`
+    pub fn node(&self) -> Node<'doc> {
+        Node::new(self.app.clone(), self.node.clone())
+    }
+}
+
+impl<'doc, R: 'static + Component> View<'doc, R> {
+    // Dispatch events of elements with `q-on:<event>="name"` attributes to `Component::handlers`,
+    //   listening for each event type once it first appears in the rendered markup
+    fn attach_named_handlers(&self) {
+        if R::handlers().is_empty() {
+            return;
+        }
+
+        let events: Vec<EventType> = {
+            let mut binding = self.binding.borrow_mut();
+            let markup = binding.last_render().unwrap_or("").to_owned();
+            EVENT_TYPES.iter()
+                .filter(|event| has_named_event(&markup, event.name()) && binding.add_named_event(event.name()))
+                .cloned()
+                .collect()
+        };
+
+        for event in events {
+            let attr = format!("q-on:{}", event.name());
+            let selector = format!("[q-on\\:{}]", event.name());
+            self.on_each(event.clone(), &selector, move |evt| {
+                let name = evt.matched.get_attr(&attr);
+                match R::handlers().into_iter().find(|&(handler_name, _)| handler_name == name) {
+                    Some((_, handler)) => handler(evt),
+                    None => warn!(target: "quasar::events", "No handler named '{}' for {}", name, attr),
+                }
+            });
+        }
+    }
+}
+
+// Whether `markup` has a `q-on:<event>` attribute
+fn has_named_event(markup: &str, event: &str) -> bool {
+    let attr = format!("q-on:{}", event);
+    markup.match_indices(&attr).any(|(i, _)| {
+        !markup[i + attr.len()..].starts_with(|c: char| c.is_alphanumeric() || c == '-')
+    })
 }
 
 impl<'doc, R: 'static + Renderable> Queryable<'doc> for View<'doc, R> {
@@ -145,36 +424,137 @@ impl<'doc, R: 'static + Renderable> Queryable<'doc> for View<'doc, R> {
         })
     }
 
-    fn bind<RR>(&self, el: &str, component: RR)
+    fn query_all(&self, el: &str) -> Vec<Self::Q> {
+        self.node.element_query_all(el).into_iter().map(|node| {
+            View {
+                app: self.app.clone(),
+                node: Rc::new(node),
+                key: self.key.clone(),
+                binding: self.binding.clone(),
+                phantom: PhantomData,
+            }
+        }).collect()
+    }
+
+    /// Bind a child component within this view, e.g. into a placeholder from `html::child`
+    ///
+    /// The child is rerendered whenever rendering this view replaces its content.
+    fn try_bind<RR>(&self, el: &str, component: RR) -> Result<View<'doc, RR>>
         where RR: 'static + Component
     {
-        let node = self.node.element_query(el).expect("querySelect returned no result");
-        let rc_node = Rc::new(node);
-        let render_node = Node::new(self.app.clone(), rc_node.clone());
-        let key = Uuid::new_v4().to_string();
+        let node = self.node.element_query(el).ok_or_else(|| Error::ElementNotFound(el.to_owned()))?;
+        self.binding.try_borrow_mut().map_err(|_| self.borrow_conflict())?;
+        let child = bind_component(&self.app, node, component)?;
+        self.binding.borrow_mut().add_child(child.view_id());
+        Ok(child)
+    }
+}
 
-        let app_context = AppContext::new(self.app.clone(), Some(TypedKey::new::<RR>(&key)));
-        rc_node.html_patch(&component.render(&render_node, &app_context));
+/// Render a component into `node`, store the binding, and run the component's `onload`
+pub fn bind_component<'doc, R>(app: &Rc<AppState<'doc>>, node: HtmlNode<'doc>, component: R) -> Result<View<'doc, R>>
+    where R: 'static + Component
+{
+    let rc_node = Rc::new(node);
+    let key = Uuid::new_v4().to_string();
+    let view_id = TypedKey::new::<R>(&key);
 
-        let binding = self.app.insert_binding(&key, component, rc_node.clone());
-        let view: View<RR> = View::new(self.app.clone(), rc_node, key, binding);
-        Component::onload(&view);
+    css::inject_styles::<R>(app);
+    let binding = app.insert_binding(&key, component, rc_node.clone());
+    if let Err(err) = app.render_binding(&view_id, &binding) {
+        // The component was never mounted, so it is not unmounted either
+        app.discard_binding(&view_id);
+        return Err(err);
     }
+
+    let view: View<R> = View::new(app.clone(), rc_node, key, binding);
+    Component::onload(&view);
+    Ok(view)
+}
+
+/// Call the `Component` hooks for a lifecycle stage of a binding
+pub fn dispatch_lifecycle<'doc, R>(app: &Rc<AppState<'doc>>,
+                                   view_id: &TypedKey,
+                                   binding: &Rc<RefCell<Binding<'doc>>>,
+                                   stage: Lifecycle)
+                                   -> bool
+    where R: 'static + Component
+{
+    let node = binding.borrow().node.clone();
+    let view: View<R> = View::new(app.clone(), node, view_id.key().to_owned(), binding.clone());
+    match stage {
+        Lifecycle::ShouldRender => {
+            // A fingerprint or snapshot only covers the component data, so changed props
+            //   or observed app data always rerender
+            let props = lookup_props(&view.node, view.data().props());
+            if !app.observed_data_changed(view_id) && props == binding.borrow().props &&
+               data_unchanged(&*view.data(), &binding.borrow()) {
+                return false;
+            }
+            return R::should_render(&view);
+        }
+        Lifecycle::BeforeRender => {
+            let props = lookup_props(&view.node, view.data().props());
+            let changed = {
+                let mut binding = binding.borrow_mut();
+                if binding.props != props {
+                    binding.props = props.clone();
+                    true
+                } else {
+                    false
+                }
+            };
+            if changed {
+                R::on_props_changed(&view, &props);
+            }
+            R::before_render(&view);
+        }
+        Lifecycle::AfterRender => {
+            view.attach_named_handlers();
+            let (fingerprint, snapshot) = {
+                let data = view.data();
+                (data.fingerprint(), data.snapshot())
+            };
+            {
+                let mut binding = binding.borrow_mut();
+                binding.fingerprint = fingerprint;
+                binding.snapshot = snapshot;
+            }
+            R::after_render(&view);
+        }
+        Lifecycle::Unmount => R::on_unmount(&view),
+    }
+    true
+}
+
+// Whether the component opted into change detection, and its data is unchanged since the last render
+fn data_unchanged<'doc, R: Component>(data: &R, binding: &Binding<'doc>) -> bool {
+    let fingerprint = data.fingerprint();
+    let snapshot = data.snapshot();
+    if fingerprint.is_none() && snapshot.is_none() {
+        return false;
+    }
+    (fingerprint.is_none() || fingerprint == binding.fingerprint) &&
+    (snapshot.is_none() || snapshot == binding.snapshot)
 }
 
 impl<'doc, R: 'static + Renderable> HasBind<'doc> for View<'doc, R> {
     type R = R;
 
-    fn data(&self) -> Ref<R> {
-        Ref::map(self.binding.borrow(), |r| r.component())
+    fn try_data(&self) -> Result<Ref<R>> {
+        let binding = self.binding.try_borrow().map_err(|_| self.borrow_conflict())?;
+        binding.component::<R>()?;
+        Ok(Ref::map(binding, |b| b.component().expect("component type already verified")))
     }
 
-    fn data_mut(&mut self) -> RefMut<R> {
+    fn try_data_mut(&mut self) -> Result<RefMut<R>> {
+        let binding = self.binding.try_borrow_mut().map_err(|_| self.borrow_conflict())?;
+        binding.component::<R>()?;
+
         // Before handing back mutable the mutable component,
         // enqueue rendering of the original view that owns this data
         let view_id = TypedKey::new::<R>(&self.key);
         self.app.enqueue_render(view_id);
-        RefMut::map(self.binding.borrow_mut(), |r| r.component_mut())
+        Ok(RefMut::map(binding, |b| b.component_mut().expect("component type already verified")))
     }
 }
 
@@ -183,6 +563,52 @@ impl<'doc, R: 'static + Renderable> HasBind<'doc> for View<'doc, R> {
 //    `RegisteredView` would be more useful for building things like routers
 // impl<'doc, R: 'static + Renderable> Drop for View<'doc, R> {
 //     fn drop(&mut self) {
-//         self.app.remove_binding::<R>(&self.key);
+//         self.unbind();
 //     }
-// }
\ No newline at end of file
+// }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use html::SafeHtml;
+
+    fn bind_markup(markup: &str) -> View<'static, SafeHtml> {
+        let app = Rc::new(AppState::new(webplatform::init()));
+        let node = app.document.element_create("div").expect("div is a valid tag");
+        bind_component(&app, node, SafeHtml::trusted(markup)).expect("markup renders")
+    }
+
+    fn ref_id(view: &View<SafeHtml>, name: &str) -> Option<String> {
+        view.r#ref(name).ok().map(|node| node.get_attr("id"))
+    }
+
+    #[test]
+    fn collects_refs_outside_child_views() {
+        let view = bind_markup("<input ref=\"name\" id=\"a\"><p><span ref=\"nested\" id=\"b\"></span></p>\
+                                <div q-child=\"item\" ref=\"slot\" id=\"c\"><b ref=\"child\"></b><i><em ref=\"deep\"></em></i></div>");
+        assert_eq!(ref_id(&view, "name"), Some("a".to_owned()));
+        assert_eq!(ref_id(&view, "nested"), Some("b".to_owned()));
+        // The placeholder belongs to this view, but not what the child view renders into it
+        assert_eq!(ref_id(&view, "slot"), Some("c".to_owned()));
+        assert_eq!(ref_id(&view, "child"), None);
+        assert_eq!(ref_id(&view, "deep"), None);
+        match view.r#ref("missing") {
+            Err(Error::MissingRef(name)) => assert_eq!(name, "missing"),
+            _ => panic!("expected a missing ref error"),
+        }
+    }
+
+    #[test]
+    fn refs_fail_while_component_data_is_mutably_borrowed() {
+        let mut view = bind_markup("<input ref=\"name\">");
+        let other = view.handle();
+        {
+            let _data = view.data_mut();
+            match other.r#ref("name") {
+                Err(Error::BorrowConflict(_)) => (),
+                _ => panic!("expected a borrow conflict"),
+            }
+        }
+        assert!(other.r#ref("name").is_ok());
+    }
+}
@@ -0,0 +1,487 @@
+//! WebSocket channels applying JSON messages to app data
+//!
+//! `AppContext::connect` opens a socket and applies each decoded message to an app data
+//!   partition with a reducer, rerendering the partition's observers:
+//!
+//! ```ignore
+//! let channel = app.connect("wss://example.com/feed", "feed", |feed: &mut Vec<Item>, item: Item| {
+//!     feed.push(item);
+//! });
+//!
+//! // later, e.g. from a handler
+//! channel.send(&Subscribe { topic: "news".to_owned() })?;
+//! ```
+//!
+//! If the connection drops, it is reopened after a delay that doubles with each failed attempt
+//!   (see `Backoff`). Sockets are opened through a `SocketTransport`, which defaults to the
+//!   browser's WebSocket and can be replaced with `QuasarApp::set_socket_transport`,
+//!   e.g. by an `EchoTransport` in tests.
+//!
+//! To try a channel against a real socket, `cargo run --example echo_server` starts a local
+//!   echo server on `ws://127.0.0.1:3012`.
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::{Rc, Weak};
+use rustc_serialize::{Decodable, Encodable};
+use rustc_serialize::json;
+use platform::{self, SocketEvent};
+use state::{AppState, TypedKey};
+use task::TaskHandle;
+use timer;
+
+use {Error, Result};
+
+/// Callbacks for the events of an open socket
+pub struct SocketEvents<'doc> {
+    pub on_open: Box<FnMut() + 'doc>,
+    pub on_message: Box<FnMut(String) + 'doc>,
+    /// Called when the connection closes or fails to open
+    pub on_close: Box<FnMut() + 'doc>,
+}
+
+/// An open (or opening) socket
+pub trait Connection {
+    fn send(&self, message: &str);
+    fn close(&self);
+}
+
+/// Opens sockets for `AppContext::connect`
+pub trait SocketTransport<'doc> {
+    fn connect(&self, url: &str, events: SocketEvents<'doc>) -> Box<Connection + 'doc>;
+}
+
+/// The browser's WebSocket
+///
+/// Socket events arrive from the browser's event loop, so only `'static` handlers can be registered.
+pub struct BrowserSocketTransport;
+
+struct BrowserConnection {
+    socket: platform::WebSocket,
+}
+
+impl SocketTransport<'static> for BrowserSocketTransport {
+    fn connect(&self, url: &str, events: SocketEvents<'static>) -> Box<Connection + 'static> {
+        let SocketEvents { mut on_open, mut on_message, mut on_close } = events;
+        let socket = platform::websocket(url, move |event| {
+            match event {
+                SocketEvent::Open => on_open(),
+                SocketEvent::Message(message) => on_message(message),
+                SocketEvent::Close => on_close(),
+            }
+        });
+        Box::new(BrowserConnection { socket: socket })
+    }
+}
+
+impl Connection for BrowserConnection {
+    fn send(&self, message: &str) {
+        self.socket.send(message);
+    }
+
+    fn close(&self) {
+        self.socket.close();
+    }
+}
+
+enum EchoEvent {
+    Open,
+    Message(String),
+    Close,
+}
+
+type EchoQueue = Rc<RefCell<VecDeque<(usize, EchoEvent)>>>;
+
+/// An in-process stand-in for an echo server, for testing without a browser or network
+///
+/// Every message sent on a socket is sent back to it. Like a real server, events are
+///   delivered asynchronously: they are queued until `deliver` is called.
+pub struct EchoTransport<'doc> {
+    sockets: RefCell<Vec<Option<SocketEvents<'doc>>>>,
+    queue: EchoQueue,
+}
+
+struct EchoConnection {
+    id: usize,
+    queue: EchoQueue,
+}
+
+impl<'doc> Default for EchoTransport<'doc> {
+    fn default() -> EchoTransport<'doc> {
+        EchoTransport::new()
+    }
+}
+
+impl<'doc> EchoTransport<'doc> {
+    pub fn new() -> EchoTransport<'doc> {
+        EchoTransport {
+            sockets: RefCell::new(vec![]),
+            queue: Rc::new(RefCell::new(VecDeque::new())),
+        }
+    }
+
+    /// Deliver queued events, including any queued while delivering
+    pub fn deliver(&self) {
+        loop {
+            let next = self.queue.borrow_mut().pop_front();
+            let (id, event) = match next {
+                Some(next) => next,
+                None => return,
+            };
+
+            // Take the callbacks out while calling them, since they may open or use sockets
+            let events = self.sockets.borrow_mut()[id].take();
+            let mut events = match events {
+                Some(events) => events,
+                None => continue,
+            };
+            match event {
+                EchoEvent::Open => (events.on_open)(),
+                EchoEvent::Message(message) => (events.on_message)(message),
+                EchoEvent::Close => {
+                    (events.on_close)();
+                    continue;
+                }
+            }
+            self.sockets.borrow_mut()[id] = Some(events);
+        }
+    }
+
+    /// Drop every open connection from the server side, as if the server went away
+    pub fn disconnect_all(&self) {
+        let sockets = self.sockets.borrow();
+        let mut queue = self.queue.borrow_mut();
+        for (id, _) in sockets.iter().enumerate().filter(|&(_, events)| events.is_some()) {
+            queue.push_back((id, EchoEvent::Close));
+        }
+    }
+
+    /// The number of sockets that have been opened and not closed
+    pub fn open_count(&self) -> usize {
+        self.sockets.borrow().iter().filter(|events| events.is_some()).count()
+    }
+}
+
+impl<'doc> SocketTransport<'doc> for EchoTransport<'doc> {
+    fn connect(&self, _url: &str, events: SocketEvents<'doc>) -> Box<Connection + 'doc> {
+        let mut sockets = self.sockets.borrow_mut();
+        let id = sockets.len();
+        sockets.push(Some(events));
+        self.queue.borrow_mut().push_back((id, EchoEvent::Open));
+        Box::new(EchoConnection { id: id, queue: self.queue.clone() })
+    }
+}
+
+impl Connection for EchoConnection {
+    fn send(&self, message: &str) {
+        self.queue.borrow_mut().push_back((self.id, EchoEvent::Message(message.to_owned())));
+    }
+
+    fn close(&self) {
+        self.queue.borrow_mut().push_back((self.id, EchoEvent::Close));
+    }
+}
+
+/// Delays between attempts to reopen a dropped connection
+///
+/// The first attempt waits `initial_ms`, and each failed attempt doubles the delay up to `max_ms`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    pub initial_ms: u32,
+    pub max_ms: u32,
+}
+
+impl Backoff {
+    /// The delay before reconnection attempt number `attempt`, counting from 0
+    pub fn delay(&self, attempt: u32) -> u32 {
+        let factor = 1u32.checked_shl(attempt).unwrap_or(u32::max_value());
+        self.initial_ms.saturating_mul(factor).min(self.max_ms)
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Backoff {
+        Backoff { initial_ms: 500, max_ms: 30_000 }
+    }
+}
+
+/// Handle to a socket opened by `AppContext::connect`
+///
+/// The channel stays open (reconnecting as needed) until it is closed, or until
+///   the view it was opened for is unbound, even if every handle is dropped.
+#[derive(Clone)]
+pub struct Channel<'doc> {
+    state: Rc<ChannelState<'doc>>,
+}
+
+struct ChannelState<'doc> {
+    url: String,
+    app: Rc<AppState<'doc>>,
+    task: TaskHandle<'doc>,
+    on_message: Box<Fn(&str) + 'doc>,
+    connection: RefCell<Option<Box<Connection + 'doc>>>,
+    open: Cell<bool>,
+    attempts: Cell<u32>,
+    backoff: Cell<Backoff>,
+    reconnect: RefCell<Option<TaskHandle<'doc>>>,
+}
+
+impl<'doc> Channel<'doc> {
+    /// Encode `message` as JSON and send it
+    ///
+    /// Returns an error if the socket is not currently open.
+    pub fn send<M: Encodable>(&self, message: &M) -> Result<()> {
+        let encoded = json::encode(message).map_err(|err| Error::Socket(format!("Failed to encode message: {}", err)))?;
+        self.send_str(&encoded)
+    }
+
+    /// Send a raw text message
+    pub fn send_str(&self, message: &str) -> Result<()> {
+        if !self.state.open.get() {
+            return Err(Error::Socket(format!("Socket to '{}' is not open", self.state.url)));
+        }
+        match *self.state.connection.borrow() {
+            Some(ref connection) => connection.send(message),
+            None => return Err(Error::Socket(format!("Socket to '{}' is not open", self.state.url))),
+        }
+        Ok(())
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state.open.get()
+    }
+
+    /// Change the delays used when reconnecting
+    pub fn set_backoff(&self, backoff: Backoff) {
+        self.state.backoff.set(backoff);
+    }
+
+    /// Close the socket without reconnecting
+    pub fn close(&self) {
+        self.state.task.cancel();
+    }
+
+    /// The task handle, which is cancelled when the channel is closed
+    pub fn task(&self) -> TaskHandle<'doc> {
+        self.state.task.clone()
+    }
+}
+
+/// Open a socket to `url`, applying each message decoded as `M` to the `T` in app data at `key`
+///
+/// The data is initialized with `T::default()` if missing. When opened for a view,
+///   the channel is closed when the view is unbound.
+pub fn connect<'doc, M, T, F>(app: &Rc<AppState<'doc>>, view_id: Option<TypedKey>, url: &str, key: &str, reducer: F) -> Channel<'doc>
+    where M: Decodable,
+          T: 'static + Default,
+          F: Fn(&mut T, M) + 'doc
+{
+    app.data_entry::<T>(key).or_insert_with(T::default);
+
+    let on_message = {
+        let app = app.clone();
+        let key = key.to_owned();
+        let url = url.to_owned();
+        move |message: &str| {
+            match json::decode::<M>(message) {
+                Ok(message) => {
+                    app.data_update(&key, |data: &mut T| reducer(data, message));
+                    app.flush_render_queue();
+                }
+                Err(err) => warn!(target: "quasar::ws", "Ignoring message from '{}': {}", url, err),
+            }
+        }
+    };
+
+    let task = TaskHandle::new();
+    let state = Rc::new(ChannelState {
+        url: url.to_owned(),
+        app: app.clone(),
+        task: task.clone(),
+        on_message: Box::new(on_message),
+        connection: RefCell::new(None),
+        open: Cell::new(false),
+        attempts: Cell::new(0),
+        backoff: Cell::new(Backoff::default()),
+        reconnect: RefCell::new(None),
+    });
+
+    // The cleanup keeps the channel alive until it is cancelled
+    {
+        let state = state.clone();
+        task.on_cancel(move || {
+            state.open.set(false);
+            if let Some(reconnect) = state.reconnect.borrow_mut().take() {
+                reconnect.cancel();
+            }
+            if let Some(connection) = state.connection.borrow_mut().take() {
+                connection.close();
+            }
+        });
+    }
+    if let Some(ref view_id) = view_id {
+        app.add_task(view_id, task.clone());
+    }
+
+    open(&state);
+    Channel { state: state }
+}
+
+fn open<'doc>(state: &Rc<ChannelState<'doc>>) {
+    let weak = Rc::downgrade(state);
+    let events = SocketEvents {
+        on_open: {
+            let weak = weak.clone();
+            Box::new(move || {
+                if let Some(state) = live(&weak) {
+                    state.open.set(true);
+                    state.attempts.set(0);
+                }
+            })
+        },
+        on_message: {
+            let weak = weak.clone();
+            Box::new(move |message| {
+                if let Some(state) = live(&weak) {
+                    (state.on_message)(&message);
+                }
+            })
+        },
+        on_close: Box::new(move || {
+            if let Some(state) = live(&weak) {
+                state.open.set(false);
+                state.connection.borrow_mut().take();
+                schedule_reconnect(&state);
+            }
+        }),
+    };
+    let connection = state.app.socket_transport().connect(&state.url, events);
+    *state.connection.borrow_mut() = Some(connection);
+}
+
+fn schedule_reconnect<'doc>(state: &Rc<ChannelState<'doc>>) {
+    let attempt = state.attempts.get();
+    state.attempts.set(attempt + 1);
+    let delay = state.backoff.get().delay(attempt);
+    info!(target: "quasar::ws", "Socket to '{}' closed, reconnecting in {}ms", state.url, delay);
+
+    let weak = Rc::downgrade(state);
+    let reconnect = timer::set_timeout(&state.app, None, delay, move |_| {
+        if let Some(state) = live(&weak) {
+            open(&state);
+        }
+    });
+    *state.reconnect.borrow_mut() = Some(reconnect);
+}
+
+// The channel state, unless the channel was closed
+fn live<'doc>(weak: &Weak<ChannelState<'doc>>) -> Option<Rc<ChannelState<'doc>>> {
+    weak.upgrade().and_then(|state| if state.task.is_done() { None } else { Some(state) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webplatform;
+    use timer::ManualTimers;
+
+    struct Feed {
+        app: Rc<AppState<'static>>,
+        echo: Rc<EchoTransport<'static>>,
+        timers: Rc<ManualTimers<'static>>,
+        channel: Channel<'static>,
+    }
+
+    fn connect_feed() -> Feed {
+        let app = Rc::new(AppState::new(webplatform::init()));
+        let echo = Rc::new(EchoTransport::new());
+        let timers = Rc::new(ManualTimers::new());
+        app.set_socket_transport(echo.clone());
+        app.set_timers(timers.clone());
+        let channel = connect(&app, None, "ws://echo", "feed", |feed: &mut Vec<String>, item: String| {
+            feed.push(item);
+        });
+        Feed { app: app, echo: echo, timers: timers, channel: channel }
+    }
+
+    fn feed(app: &AppState) -> Vec<String> {
+        app.data::<Vec<String>>("feed").expect("feed was not initialized").clone()
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let backoff = Backoff { initial_ms: 100, max_ms: 1000 };
+        let delays: Vec<u32> = (0..6).map(|attempt| backoff.delay(attempt)).collect();
+        assert_eq!(delays, vec![100, 200, 400, 800, 1000, 1000]);
+        assert_eq!(backoff.delay(40), 1000);
+        assert_eq!(Backoff::default().delay(0), 500);
+    }
+
+    #[test]
+    fn sends_only_once_open() {
+        let Feed { echo, channel, .. } = connect_feed();
+        assert!(!channel.is_open());
+        assert!(channel.send_str("early").is_err());
+
+        echo.deliver();
+        assert!(channel.is_open());
+        assert!(channel.send(&"hello".to_owned()).is_ok());
+    }
+
+    #[test]
+    fn applies_decoded_messages_with_reducer() {
+        let Feed { app, echo, channel, .. } = connect_feed();
+        assert_eq!(feed(&app), Vec::<String>::new());
+
+        echo.deliver();
+        channel.send(&"first".to_owned()).unwrap();
+        channel.send(&"second".to_owned()).unwrap();
+        echo.deliver();
+        assert_eq!(feed(&app), vec!["first".to_owned(), "second".to_owned()]);
+    }
+
+    #[test]
+    fn ignores_undecodable_messages() {
+        let Feed { app, echo, channel, .. } = connect_feed();
+        echo.deliver();
+        channel.send_str("not json").unwrap();
+        channel.send_str("{\"label\": \"object\"}").unwrap();
+        channel.send(&"ok".to_owned()).unwrap();
+        echo.deliver();
+        assert_eq!(feed(&app), vec!["ok".to_owned()]);
+    }
+
+    #[test]
+    fn reconnects_after_backoff_delay() {
+        let Feed { echo, timers, channel, .. } = connect_feed();
+        channel.set_backoff(Backoff { initial_ms: 100, max_ms: 1000 });
+        echo.deliver();
+
+        echo.disconnect_all();
+        echo.deliver();
+        assert!(!channel.is_open());
+        assert_eq!(echo.open_count(), 0);
+
+        timers.advance(99);
+        assert_eq!(echo.open_count(), 0);
+        timers.advance(1);
+        assert_eq!(echo.open_count(), 1);
+        echo.deliver();
+        assert!(channel.is_open());
+    }
+
+    #[test]
+    fn close_does_not_reconnect() {
+        let Feed { echo, timers, channel, .. } = connect_feed();
+        echo.deliver();
+
+        channel.close();
+        echo.deliver();
+        timers.advance(60_000);
+        assert!(!channel.is_open());
+        assert!(channel.task().is_cancelled());
+        assert_eq!(echo.open_count(), 0);
+        assert_eq!(timers.pending(), 0);
+        assert!(channel.send_str("late").is_err());
+    }
+}
@@ -0,0 +1,46 @@
+//! `#[derive(Template)]` expanded and rendered as a component; run with `cargo test --features derive`
+#![cfg(feature = "derive")]
+
+extern crate quasar;
+extern crate rustc_serialize;
+
+use quasar::{Component, Queryable, Template, View};
+
+#[derive(RustcEncodable, Template)]
+#[template_string = "<p data-value=\"{{value}}\"></p>"]
+struct Wrapper<T> {
+    value: T,
+}
+
+impl<T: 'static + rustc_serialize::Encodable> Component for Wrapper<T> {
+    fn onload(_view: &View<Self>) {}
+}
+
+#[derive(RustcEncodable, Template)]
+#[template_string = "<ul>{{#for item in items}}{{> item item}}{{/for}}</ul>"]
+#[template_partials(item = "tests/item.html")]
+struct List {
+    items: Vec<String>,
+}
+
+impl Component for List {
+    fn onload(_view: &View<Self>) {}
+}
+
+#[test]
+fn renders_generic_types() {
+    let app = quasar::init();
+    app.bind("body", Wrapper { value: 5u32 });
+    assert_eq!(app.query("p").unwrap().get_attr("data-value"), "5");
+
+    app.bind("body", Wrapper { value: "five".to_owned() });
+    assert_eq!(app.query("p").unwrap().get_attr("data-value"), "five");
+}
+
+#[test]
+fn renders_partials_from_files() {
+    let app = quasar::init();
+    app.bind("body", List { items: vec!["a".to_owned(), "b".to_owned()] });
+    let items: Vec<_> = app.query_all("li").iter().map(|li| li.get_attr("data-item")).collect();
+    assert_eq!(items, vec!["a", "b"]);
+}
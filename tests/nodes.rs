@@ -0,0 +1,111 @@
+//! Creating, inserting and removing elements through `Node`, and reading form controls
+
+extern crate quasar;
+
+use quasar::{Error, Node, Queryable};
+
+fn ids(nodes: Vec<Node>) -> Vec<String> {
+    nodes.iter().map(|node| node.get_attr("id")).collect()
+}
+
+#[test]
+fn appends_created_elements() {
+    let app = quasar::init();
+    let list = app.create_element("ul").unwrap();
+    list.set_attr("id", "list");
+    app.query("body").unwrap().append_child(&list);
+
+    for id in &["a", "b"] {
+        let item = list.create_element("li").unwrap();
+        item.set_attr("id", id);
+        list.append_child(&item);
+    }
+    assert_eq!(ids(app.query_all("#list > li")), vec!["a", "b"]);
+
+    // Appending an attached element moves it
+    let first = app.query("#a").unwrap();
+    list.append_child(&first);
+    assert_eq!(ids(list.children()), vec!["b", "a"]);
+}
+
+#[test]
+fn removes_elements() {
+    let app = quasar::init();
+    let item = app.create_element("p").unwrap();
+    item.set_attr("id", "gone");
+    app.query("body").unwrap().append_child(&item);
+    assert!(app.query("#gone").is_some());
+
+    item.remove();
+    assert!(app.query("#gone").is_none());
+    assert!(item.parent().is_none());
+}
+
+#[test]
+fn rejects_invalid_tags() {
+    let app = quasar::init();
+    match app.create_element("not a tag") {
+        Err(Error::InvalidElement(tag)) => assert_eq!(tag, "not a tag"),
+        _ => panic!("expected an invalid element error"),
+    }
+}
+
+// A form with radio buttons in two groups, one of whose names needs escaping in a selector
+fn radio_form<'doc>(app: &quasar::QuasarApp<'doc>) -> Node<'doc> {
+    let form = app.create_element("form").unwrap();
+    for &(name, value) in &[("size", "s"), ("size", "m"), ("say \"hi\"", "yes"), ("say \"hi\"", "no")] {
+        let radio = app.create_element("input").unwrap();
+        radio.set_attr("type", "radio");
+        radio.set_attr("name", name);
+        radio.set_attr("value", value);
+        form.append_child(&radio);
+    }
+    app.query("body").unwrap().append_child(&form);
+    form
+}
+
+#[test]
+fn reads_the_checked_radio_of_a_group() {
+    let app = quasar::init();
+    let form = radio_form(&app);
+    assert_eq!(form.radio_value("size"), None);
+
+    let radios = form.query_all("input");
+    radios[1].set_checked(true);
+    radios[2].set_checked(true);
+    assert_eq!(form.radio_value("size"), Some("m".to_owned()));
+    assert_eq!(form.radio_value("say \"hi\""), Some("yes".to_owned()));
+    assert_eq!(form.radio_value("missing"), None);
+}
+
+#[test]
+fn reads_selected_options() {
+    let app = quasar::init();
+    let select = app.create_element("select").unwrap();
+    for value in &["a", "b", "c"] {
+        let option = app.create_element("option").unwrap();
+        option.set_attr("value", value);
+        select.append_child(&option);
+    }
+    assert_eq!(select.selected_values(), Vec::<String>::new());
+
+    let options = select.query_all("option");
+    options[0].set_prop("selected", true);
+    options[2].set_prop("selected", true);
+    assert_eq!(select.selected_values(), vec!["a", "c"]);
+
+    select.set_selected_index(Some(1));
+    assert_eq!(select.selected_index(), Some(1));
+    select.set_selected_index(None);
+    assert_eq!(select.selected_index(), None);
+}
+
+#[test]
+fn reads_empty_number_inputs_as_none() {
+    let app = quasar::init();
+    let input = app.create_element("input").unwrap();
+    input.set_prop("valueAsNumber", ::std::f64::NAN);
+    assert_eq!(input.value_as_number(), None);
+    input.set_prop("valueAsNumber", 4.5);
+    assert_eq!(input.value_as_number(), Some(4.5));
+}
@@ -0,0 +1,99 @@
+//! Queries from the app, and from the context of a view
+
+extern crate quasar;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use quasar::{AppContext, Component, HasBind, ManualTimers, Node, Queryable, Renderable, Result, View};
+
+struct Panel {
+    label: &'static str,
+}
+
+impl Renderable for Panel {
+    fn render(&self, _node: &Node, _app: &AppContext) -> Result<String> {
+        Ok(format!("<p class=\"label\" data-label=\"{}\"></p>", self.label))
+    }
+}
+
+impl Component for Panel {
+    fn onload(_view: &View<Self>) {}
+}
+
+fn labels(nodes: Vec<Node>) -> Vec<String> {
+    nodes.iter().map(|node| node.get_attr("data-label")).collect()
+}
+
+// Two panels bound side by side, and timers to run code in the context of the first
+fn panels() -> (quasar::QuasarApp<'static>, View<'static, Panel>, Rc<ManualTimers<'static>>) {
+    let mut app = quasar::init();
+    let timers = Rc::new(ManualTimers::new());
+    app.set_timers(timers.clone());
+    let body = app.query("body").unwrap();
+    for id in &["first", "second"] {
+        let panel = app.create_element("div").unwrap();
+        panel.set_attr("id", id);
+        body.append_child(&panel);
+    }
+    let first = app.bind("#first", Panel { label: "a" });
+    app.bind("#second", Panel { label: "b" });
+    (app, first, timers)
+}
+
+#[test]
+fn app_queries_search_the_document() {
+    let (app, _, _) = panels();
+    assert_eq!(labels(app.query_all(".label")), vec!["a", "b"]);
+}
+
+#[test]
+fn view_queries_stay_within_the_view() {
+    let (_, first, timers) = panels();
+    let found = Rc::new(RefCell::new(None));
+    {
+        let found = found.clone();
+        first.set_timeout(0, move |_, app| {
+            let scoped = labels(app.query_all(".label"));
+            let document = labels(app.query_all_document(".label"));
+            let last = app.query_document("#second .label").map(|node| node.get_attr("data-label"));
+            *found.borrow_mut() = Some((scoped, document, last));
+        });
+    }
+    timers.advance(0);
+
+    let (scoped, document, last) = found.borrow_mut().take().expect("timeout did not fire");
+    assert_eq!(scoped, vec!["a"]);
+    assert_eq!(document, vec!["a", "b"]);
+    assert_eq!(last, Some("b".to_owned()));
+}
+
+#[test]
+fn view_queries_work_while_component_data_is_borrowed() {
+    let (_, first, timers) = panels();
+    let found = Rc::new(RefCell::new(vec![]));
+    {
+        let found = found.clone();
+        first.set_timeout(0, move |mut view, app| {
+            let data = view.data_mut();
+            *found.borrow_mut() = labels(app.query_all(".label"));
+            drop(data);
+        });
+    }
+    timers.advance(0);
+    assert_eq!(*found.borrow(), vec!["a"]);
+}
+
+#[test]
+fn queries_of_unbound_views_match_nothing() {
+    let (_, first, timers) = panels();
+    let found = Rc::new(RefCell::new(None));
+    {
+        let found = found.clone();
+        first.set_timeout(0, move |view, app| {
+            view.unbind();
+            *found.borrow_mut() = Some(app.query_all(".label").len());
+        });
+    }
+    timers.advance(0);
+    assert_eq!(*found.borrow(), Some(0));
+}
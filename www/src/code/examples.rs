@@ -47,10 +47,10 @@ impl Example {
 }
 
 impl Renderable for Example {
-    fn render(&self, _node: &Node, app: &AppContext) -> String {
-        let template = app.data::<Template>("template").expect("Failed to get 'template' data");
+    fn render(&self, _node: &Node, app: &AppContext) -> Result<String> {
+        let template = app.try_data::<Template>("template")?;
         println!("RENDERING {} {}", &*template, self.name());
-        CodeExample::new(self.clone(), template.clone()).to_string()
+        Ok(CodeExample::new(self.clone(), template.clone()).to_string())
     }
 }
 
@@ -4,6 +4,8 @@ use quasar::*;
 #[template = "src/counter/bart/counter.html"]
 pub struct CounterData { count: u32 }
 
+bart_renderable!(CounterData);
+
 impl Component for CounterData {
     fn onload(view: &View<Self>) {
         view.on_each(EventType::Click, "button", |mut evt| {
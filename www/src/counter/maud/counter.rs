@@ -1,15 +1,16 @@
 use quasar::*;
+use maud::Markup;
 
 #[derive(Default)]
 pub struct CounterData {
     pub count: u32,
 }
 
-impl Renderable for CounterData {
-    fn render(&self, _node: &Node, _app: &AppContext) -> String {
-        (html! {
+impl RenderMarkup for CounterData {
+    fn markup(&self, _node: &Node, _app: &AppContext) -> Markup {
+        html! {
             p { "Count: " (self.count) }
             button { "+1" }
-        }).into_string()
+        }
     }
 }
\ No newline at end of file
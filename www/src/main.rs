@@ -7,6 +7,10 @@ extern crate maud;
 #[macro_use]
 extern crate bart_derive;
 
+#[macro_use]
+extern crate log;
+
+#[macro_use]
 extern crate quasar;
 extern crate rustc_serialize;
 
@@ -2,23 +2,34 @@
 #[template = "src/todo/bart/todo.html"]
 pub struct TodoList {
     pub items: Vec<TodoItem>,
+    pub next_id: u32,
 }
 
+bart_renderable!(TodoList);
+
 impl TodoList {
     pub fn new() -> TodoList {
         TodoList {
-            items: vec![TodoItem::new("Blog about Quasar")]
+            items: vec![TodoItem::new(0, "Blog about Quasar")],
+            next_id: 1,
         }
     }
+
+    pub fn add(&mut self, label: &str) {
+        let item = TodoItem::new(self.next_id, label);
+        self.next_id += 1;
+        self.items.push(item);
+    }
 }
 
 pub struct TodoItem {
+    pub id: u32,
     pub label: String,
     pub complete: bool,
 }
 
 impl TodoItem {
-    pub fn new(label: &str) -> TodoItem {
-        TodoItem { label: label.to_string(), complete: false }
+    pub fn new(id: u32, label: &str) -> TodoItem {
+        TodoItem { id: id, label: label.to_string(), complete: false }
     }
 }
@@ -8,17 +8,19 @@ impl Component for TodoList {
             .on(EventType::Click, |mut evt| {
                 match evt.app.query("#message") {
                     Some(node) => {
-                        let item = TodoItem::new(&node.get("value"));
-                        evt.binding.data_mut().items.push(item);
+                        evt.binding.data_mut().add(&node.get("value"));
                     }
-                    None => println!("Query #message returned nothing.")
+                    None => warn!("Query #message returned nothing.")
                 }
             });
 
         view.on_each(EventType::Change, ".todo-item input", |mut evt| {
             let state = evt.target.checked();
+            let key = evt.key.clone();
             let mut item_list = evt.binding.data_mut();
-            item_list.items[evt.index].complete = state;
+            if let Some(item) = key.and_then(|key| key.find_mut(&mut item_list.items, |item| item.id)) {
+                item.complete = state;
+            }
         });
     }
 }
\ No newline at end of file
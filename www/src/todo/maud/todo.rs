@@ -1,35 +1,45 @@
 use quasar::*;
+use maud::Markup;
 
 pub struct TodoItem {
+    pub id: u32,
     pub label: String,
     pub complete: bool,
 }
 
 pub struct TodoList {
     pub items: Vec<TodoItem>,
+    pub next_id: u32,
 }
 
 impl TodoList{
     fn new() -> TodoList {
         TodoList {
-            items: vec![TodoItem::new("Blog about Quasar")]
+            items: vec![TodoItem::new(0, "Blog about Quasar")],
+            next_id: 1,
         }
     }
+
+    pub fn add(&mut self, label: &str) {
+        let item = TodoItem::new(self.next_id, label);
+        self.next_id += 1;
+        self.items.push(item);
+    }
 }
 
 impl TodoItem {
-    pub fn new(label: &str) -> TodoItem {
-        TodoItem { label: label.to_string(), complete: false }
+    pub fn new(id: u32, label: &str) -> TodoItem {
+        TodoItem { id: id, label: label.to_string(), complete: false }
     }
 }
 
-impl Renderable for TodoList {
-    fn render(&self, _node: &Node, _app: &AppContext) -> String {
-        (html! {
+impl RenderMarkup for TodoList {
+    fn markup(&self, _node: &Node, _app: &AppContext) -> Markup {
+        html! {
             h3 { "To Do List (" (self.items.len()) " items)" }
             ul id="todo-ul" {
                 @for item in &self.items {
-                    li class={ "todo-item " (item.complete) } {
+                    li class={ "todo-item " (item.complete) } data-key=(item.id) {
                         input type="checkbox" checked?[item.complete]
                         (item.label)
                     }
@@ -37,7 +47,7 @@ impl Renderable for TodoList {
             }
             input id="message" type="text"
             button { "Add" }
-        }).into_string()
+        }
     }
 }
 
@@ -2,6 +2,7 @@ use quasar::*;
 
 #[derive(Debug, RustcEncodable)]
 struct TodoItem {
+    id: u32,
     label: String,
     complete: bool,
 }
@@ -9,17 +10,18 @@ struct TodoItem {
 #[derive(Debug, RustcEncodable)]
 struct TodoList {
     items: Vec<TodoItem>,
+    next_id: u32,
 }
 
 pub fn init(app: &QuasarApp) {
     let todo_list = Component {
         props: vec![],
-        data: TodoList{ items: vec![] },
+        data: TodoList{ items: vec![], next_id: 0 },
         template: compile_str(r##"
           <h3>To Do List</h3>
           <ul id="todo-list">
             {{#items}}
-                <li class="todo-item {{#complete}}complete{{/complete}}">
+                <li class="todo-item {{#complete}}complete{{/complete}}" data-key="{{ id }}">
                     <input type="checkbox" {{#complete}}checked{{/complete}}>
                     {{ label }}
                 </li>
@@ -36,17 +38,22 @@ pub fn init(app: &QuasarApp) {
         .on(EventType::Click, |mut evt| {
             match evt.binding.query("#message") {
                 Some(node) => {
-                    let item = TodoItem { label: node.get("value"), complete: false };
-                    evt.binding.data_mut().items.push(item);
+                    let mut list = evt.binding.data_mut();
+                    let item = TodoItem { id: list.next_id, label: node.get("value"), complete: false };
+                    list.next_id += 1;
+                    list.items.push(item);
                 }
-                None => println!("Query for #message returned nothing.")
+                None => warn!("Query for #message returned nothing.")
             }
         }
     );
 
     view.on_each(EventType::Change, ".todo-item input", |mut evt| {
         let state = evt.target.checked();
+        let key = evt.key.clone();
         let mut item_list = evt.binding.data_mut();
-        item_list.items[evt.index].complete = state;
+        if let Some(item) = key.and_then(|key| key.find_mut(&mut item_list.items, |item| item.id)) {
+            item.complete = state;
+        }
     });
 }
\ No newline at end of file